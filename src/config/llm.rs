@@ -1,40 +1,462 @@
-use once_cell::sync::Lazy;
-use std::env;
+use std::collections::HashMap;
+use std::time::Duration;
 
-use crate::models::api::LlmBackend;
+use crate::config::settings::{Settings, SettingsReader};
+use crate::models::api::{LlmBackend, LlmProvider, ModelRoute};
+use crate::parser::cache::CacheMode;
+use crate::parser::cassette::CassetteMode;
+use crate::parser::ollama::SectionParser;
+use crate::parser::router::RoutingStrategy;
 
 #[derive(Debug, Clone)]
 pub struct LlmConfig {
-    pub backend: LlmBackend,
     pub openai_api_key: Option<String>,
     pub openai_model: String,
+    /// Base URL of the OpenAI API, overridable to point at a mock server
+    pub openai_url: String,
     pub ollama_model: String,
     pub ollama_url: String,
+    pub ollama_temperature: Option<f32>,
+    pub ollama_seed: Option<i64>,
+    /// Fixed context window; computed from the prompt size when `None`
+    pub ollama_num_ctx: Option<u32>,
+    /// Upper bound for the computed context window
+    pub ollama_max_ctx: u32,
+    pub ollama_num_predict: Option<i32>,
+    pub ollama_keep_alive: Option<String>,
+    /// Pull `ollama_model` during preflight when the server does not have it
+    pub ollama_auto_pull: bool,
+    /// GGUF file of the in-process model (`local-llm` feature)
+    pub local_model: String,
+    /// `tokenizer.json` of the local model; next to the GGUF file when `None`
+    pub local_tokenizer: Option<String>,
+    /// Longest reply the local model may generate, in tokens
+    pub local_max_tokens: usize,
+    pub cache_dir: String,
+    pub cache_mode: CacheMode,
+    /// File of recorded backend exchanges, used when `cassette_mode` is not `Off`
+    pub cassette_path: String,
+    pub cassette_mode: CassetteMode,
+    /// Providers requests are routed to, load-balanced ones first, then fallbacks in order.
+    /// Never empty: defaults to the `LLM_BACKEND` provider described by the fields above.
+    pub providers: Vec<LlmProvider>,
+    pub routing: RoutingStrategy,
+    /// Consecutive failures after which a provider is taken out of rotation
+    pub provider_max_failures: u32,
+    /// How long a failing provider stays out of rotation
+    pub provider_cooldown: Duration,
+    /// Section template name (e.g. `office_bearers`) → model it is sent to,
+    /// from `LLM_MODEL_<SECTION>`
+    pub section_models: HashMap<String, ModelRoute>,
+    /// Section template name → model to retry with when the first reply fails
+    /// validation, from `LLM_ESCALATE_<SECTION>`
+    pub section_escalations: HashMap<String, ModelRoute>,
+    /// Escalation model for sections without their own, from `LLM_ESCALATION_MODEL`
+    pub escalation_model: Option<ModelRoute>,
 }
 
 impl LlmConfig {
-    fn from_env() -> Self {
-        let backend = match env::var("LLM_BACKEND")
-            .unwrap_or_else(|_| "ollama".to_string())
-            .to_lowercase()
-            .as_str()
-        {
-            "openai" => LlmBackend::OpenAI,
-            _ => LlmBackend::Ollama,
-        };
+    /// Backend of the first provider, which prompts are tailored to
+    pub fn primary_backend(&self) -> LlmBackend {
+        self.providers[0].backend
+    }
 
-        Self {
+    /// Default provider of a backend, from the single-backend settings
+    pub fn default_provider(&self, backend: LlmBackend) -> LlmProvider {
+        let (url, model) = match backend {
+            LlmBackend::Ollama => (self.ollama_url.as_str(), &self.ollama_model),
+            LlmBackend::OpenAI => (self.openai_url.as_str(), &self.openai_model),
+            LlmBackend::Local => ("in-process", &self.local_model),
+        };
+        LlmProvider {
             backend,
-            openai_api_key: env::var("OPENAI_API_KEY").ok(),
-            openai_model: env::var("OPENAI_MODEL").unwrap_or_else(|_| "gpt-4.1-mini".to_string()),
-            ollama_model: env::var("OLLAMA_MODEL").unwrap_or_else(|_| "qwen2.5:3b".to_string()),
-            ollama_url: env::var("OLLAMA_URL")
-                .unwrap_or_else(|_| "http://localhost:11434".to_string()),
+            url: url.to_string(),
+            model: model.clone(),
+            api_key: match backend {
+                LlmBackend::OpenAI => self.openai_api_key.clone(),
+                LlmBackend::Ollama | LlmBackend::Local => None,
+            },
+            fallback: false,
+        }
+    }
+
+    /// Tokenizer of a local model: `local_tokenizer`, or else the
+    /// `tokenizer.json` next to the GGUF file
+    pub fn local_tokenizer_for(&self, model: &str) -> String {
+        self.local_tokenizer.clone().unwrap_or_else(|| {
+            std::path::Path::new(model)
+                .with_file_name("tokenizer.json")
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    /// Parse a comma-separated provider list, e.g.
+    /// `ollama@http://gpu1:11434, ollama=qwen2.5:7b@http://gpu2:11434, openai`.
+    /// Each entry is `backend[=model][@url]`; a missing model or URL is taken
+    /// from the backend's own settings. Entries with an unknown backend are
+    /// skipped and reported in `errors`.
+    fn parse_providers(
+        &self,
+        spec: &str,
+        fallback: bool,
+        errors: &mut Vec<String>,
+    ) -> Vec<LlmProvider> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| {
+                let (head, url) = match entry.split_once('@') {
+                    Some((head, url)) => (head, Some(url)),
+                    None => (entry, None),
+                };
+                let (backend, model) = match head.split_once('=') {
+                    Some((backend, model)) => (backend, Some(model)),
+                    None => (head, None),
+                };
+
+                let Some(backend) = LlmBackend::parse(backend) else {
+                    errors.push(format!("LLM provider {:?}: unknown backend", entry));
+                    return None;
+                };
+                let mut provider = self.default_provider(backend);
+                if let Some(url) = url {
+                    provider.url = url.trim_end_matches('/').to_string();
+                }
+                if let Some(model) = model {
+                    provider.model = model.to_string();
+                }
+                provider.fallback = fallback;
+                Some(provider)
+            })
+            .collect()
+    }
+
+    /// Read the LLM settings, recording invalid values in `reader`
+    pub(crate) fn from_settings(reader: &mut SettingsReader) -> Self {
+        let backend = LlmBackend::parse(&reader.choice("LLM_BACKEND", &BACKENDS))
+            .unwrap_or(LlmBackend::Ollama);
+        let cache = reader.choice(
+            "LLM_CACHE",
+            &["on", "true", "off", "false", "bypass", "refresh", "evict"],
+        );
+        let cassette_mode = reader.choice("LLM_CASSETTE_MODE", &["off", "record", "replay"]);
+        let routing = reader.choice(
+            "LLM_ROUTING",
+            &["round-robin", "round_robin", "least-busy", "least_busy"],
+        );
+
+        let mut config = Self {
+            openai_api_key: reader.optional("OPENAI_API_KEY"),
+            openai_model: reader.string("OPENAI_MODEL", "gpt-4.1-mini"),
+            openai_url: reader.string("OPENAI_URL", "https://api.openai.com/v1"),
+            ollama_model: reader.string("OLLAMA_MODEL", "qwen2.5:3b"),
+            ollama_url: reader.string("OLLAMA_URL", "http://localhost:11434"),
+            ollama_temperature: reader.option("OLLAMA_TEMPERATURE", Some(0.0)),
+            ollama_seed: reader.option("OLLAMA_SEED", Some(42)),
+            ollama_num_ctx: reader.option("OLLAMA_NUM_CTX", None),
+            ollama_max_ctx: reader.parse("OLLAMA_MAX_CTX", 32768),
+            ollama_num_predict: reader.option("OLLAMA_NUM_PREDICT", None),
+            ollama_keep_alive: reader.optional("OLLAMA_KEEP_ALIVE"),
+            ollama_auto_pull: reader.flag("OLLAMA_AUTO_PULL"),
+            local_model: reader.string("LOCAL_MODEL", "models/qwen2.5-1.5b-instruct-q4_k_m.gguf"),
+            local_tokenizer: reader.optional("LOCAL_TOKENIZER"),
+            local_max_tokens: reader.parse("LOCAL_MAX_TOKENS", 2048),
+            cache_dir: reader.string("LLM_CACHE_DIR", ".llm_cache"),
+            cache_mode: CacheMode::parse(&cache),
+            cassette_path: reader.string("LLM_CASSETTE", "tests/cassettes/llm.jsonl"),
+            cassette_mode: CassetteMode::parse(&cassette_mode),
+            providers: Vec::new(),
+            routing: RoutingStrategy::parse(&routing),
+            provider_max_failures: reader.parse("LLM_PROVIDER_MAX_FAILURES", 3),
+            provider_cooldown: Duration::from_secs(reader.parse("LLM_PROVIDER_COOLDOWN", 60)),
+            section_models: HashMap::new(),
+            section_escalations: HashMap::new(),
+            escalation_model: None,
+        };
+
+        let settings = reader.settings();
+        let mut providers = config.parse_providers(
+            settings.get("LLM_PROVIDERS").unwrap_or_default(),
+            false,
+            &mut reader.errors,
+        );
+        if providers.is_empty() {
+            providers.push(config.default_provider(backend));
+        }
+        providers.extend(config.parse_providers(
+            settings.get("LLM_FALLBACK_PROVIDERS").unwrap_or_default(),
+            true,
+            &mut reader.errors,
+        ));
+        config.providers = providers;
+
+        let primary = config.primary_backend();
+        config.section_models = section_routes(
+            settings.with_prefix("LLM_MODEL_"),
+            "LLM_MODEL_",
+            primary,
+            &mut reader.errors,
+        );
+        config.section_escalations = section_routes(
+            settings.with_prefix("LLM_ESCALATE_"),
+            "LLM_ESCALATE_",
+            primary,
+            &mut reader.errors,
+        );
+        config.escalation_model = reader.optional("LLM_ESCALATION_MODEL").and_then(|v| {
+            let route = ModelRoute::parse(&v, primary);
+            if route.is_none() {
+                reader.errors.push(format!(
+                    "LLM_ESCALATION_MODEL: expected backend=model, got {:?}",
+                    v
+                ));
+            }
+            route
+        });
+        config
+    }
+
+    /// Problems that make the LLM settings unusable
+    pub(crate) fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for provider in &self.providers {
+            if provider.backend != LlmBackend::Local
+                && !(provider.url.starts_with("http://") || provider.url.starts_with("https://"))
+            {
+                errors.push(format!(
+                    "Provider {}: URL must start with http:// or https://",
+                    provider.label()
+                ));
+            }
+            if provider.backend == LlmBackend::OpenAI
+                && provider.api_key.is_none()
+                && self.cassette_mode != CassetteMode::Replay
+            {
+                errors.push(format!(
+                    "Provider {}: OPENAI_API_KEY is not set",
+                    provider.label()
+                ));
+            }
+        }
+        if let Some(num_ctx) = self.ollama_num_ctx {
+            if num_ctx > self.ollama_max_ctx {
+                errors.push(format!(
+                    "OLLAMA_NUM_CTX ({}) is larger than OLLAMA_MAX_CTX ({})",
+                    num_ctx, self.ollama_max_ctx
+                ));
+            }
+        }
+        if self.local_max_tokens == 0 {
+            errors.push("LOCAL_MAX_TOKENS must be positive".to_string());
+        }
+        if self.provider_max_failures == 0 {
+            errors.push("LLM_PROVIDER_MAX_FAILURES must be positive".to_string());
+        }
+        errors
+    }
+
+    /// The settings this configuration was read from, with defaults filled
+    /// in. The API key is left out.
+    pub(crate) fn to_settings(&self, settings: &mut Settings) {
+        let optional = |v: Option<String>| v.unwrap_or_default();
+        let providers = |fallback: bool| {
+            self.providers
+                .iter()
+                .filter(|p| p.fallback == fallback)
+                .map(LlmProvider::spec)
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        settings.set("LLM_BACKEND", self.primary_backend().as_str());
+        settings.set("OPENAI_MODEL", &self.openai_model);
+        settings.set("OPENAI_URL", &self.openai_url);
+        settings.set("OLLAMA_MODEL", &self.ollama_model);
+        settings.set("OLLAMA_URL", &self.ollama_url);
+        settings.set(
+            "OLLAMA_TEMPERATURE",
+            optional(self.ollama_temperature.map(|v| v.to_string())),
+        );
+        settings.set(
+            "OLLAMA_SEED",
+            optional(self.ollama_seed.map(|v| v.to_string())),
+        );
+        settings.set(
+            "OLLAMA_NUM_CTX",
+            optional(self.ollama_num_ctx.map(|v| v.to_string())),
+        );
+        settings.set("OLLAMA_MAX_CTX", self.ollama_max_ctx.to_string());
+        settings.set(
+            "OLLAMA_NUM_PREDICT",
+            optional(self.ollama_num_predict.map(|v| v.to_string())),
+        );
+        settings.set(
+            "OLLAMA_KEEP_ALIVE",
+            optional(self.ollama_keep_alive.clone()),
+        );
+        settings.set("OLLAMA_AUTO_PULL", self.ollama_auto_pull.to_string());
+        settings.set("LOCAL_MODEL", &self.local_model);
+        settings.set("LOCAL_TOKENIZER", optional(self.local_tokenizer.clone()));
+        settings.set("LOCAL_MAX_TOKENS", self.local_max_tokens.to_string());
+        settings.set("LLM_CACHE", self.cache_mode.as_str());
+        settings.set("LLM_CACHE_DIR", &self.cache_dir);
+        settings.set("LLM_CASSETTE", &self.cassette_path);
+        settings.set("LLM_CASSETTE_MODE", self.cassette_mode.as_str());
+        settings.set("LLM_PROVIDERS", providers(false));
+        settings.set("LLM_FALLBACK_PROVIDERS", providers(true));
+        settings.set("LLM_ROUTING", self.routing.as_str());
+        settings.set(
+            "LLM_PROVIDER_MAX_FAILURES",
+            self.provider_max_failures.to_string(),
+        );
+        settings.set(
+            "LLM_PROVIDER_COOLDOWN",
+            self.provider_cooldown.as_secs().to_string(),
+        );
+        settings.set(
+            "LLM_ESCALATION_MODEL",
+            optional(self.escalation_model.as_ref().map(ModelRoute::spec)),
+        );
+        for (section, route) in &self.section_models {
+            settings.set(format!("LLM_MODEL_{}", section), route.spec());
+        }
+        for (section, route) in &self.section_escalations {
+            settings.set(format!("LLM_ESCALATE_{}", section), route.spec());
         }
     }
 }
 
-pub static LLM_CONFIG: Lazy<LlmConfig> = Lazy::new(|| {
-    dotenvy::dotenv().ok();
-    LlmConfig::from_env()
-});
+/// Accepted values of `LLM_BACKEND`
+const BACKENDS: [&str; 3] = ["ollama", "openai", "local"];
+
+/// Collect per-section model routes from settings named `<prefix><SECTION>`,
+/// e.g. `LLM_MODEL_OFFICE_BEARERS=ollama=qwen2.5:14b`, keyed by the lowercase
+/// section template name. Names that are not a section's template name are
+/// reported rather than ignored.
+fn section_routes(
+    vars: impl Iterator<Item = (String, String)>,
+    prefix: &str,
+    default_backend: LlmBackend,
+    errors: &mut Vec<String>,
+) -> HashMap<String, ModelRoute> {
+    let sections = SectionParser::template_names();
+    vars.filter_map(|(name, value)| {
+        let section = name.strip_prefix(prefix)?.to_lowercase();
+        if !sections.contains(&section.as_str()) {
+            errors.push(format!(
+                "{}: unknown section {:?}, expected one of {}",
+                name,
+                section,
+                sections.join(", ")
+            ));
+            return None;
+        }
+        let route = ModelRoute::parse(&value, default_backend);
+        if route.is_none() {
+            errors.push(format!("{}: expected backend=model, got {:?}", name, value));
+        }
+        Some((section, route?))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(pairs: &[(&str, &str)]) -> (LlmConfig, Vec<String>) {
+        let settings: Settings = pairs.iter().copied().collect();
+        let mut reader = SettingsReader::new(&settings);
+        let config = LlmConfig::from_settings(&mut reader);
+        (config, reader.errors)
+    }
+
+    #[test]
+    fn test_parse_providers() {
+        let (config, _) = config(&[("OPENAI_API_KEY", "sk-test")]);
+        let mut errors = Vec::new();
+        let providers = config.parse_providers(
+            "ollama@http://gpu1:11434/, ollama=qwen2.5:7b@http://gpu2:11434,, openai, llama@x",
+            false,
+            &mut errors,
+        );
+
+        assert_eq!(providers.len(), 3);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(providers[0].url, "http://gpu1:11434");
+        assert_eq!(providers[0].model, "qwen2.5:3b");
+        assert_eq!(providers[1].model, "qwen2.5:7b");
+        assert_eq!(providers[1].url, "http://gpu2:11434");
+        assert_eq!(providers[2].backend, LlmBackend::OpenAI);
+        assert_eq!(providers[2].url, "https://api.openai.com/v1");
+        assert_eq!(providers[2].api_key.as_deref(), Some("sk-test"));
+        assert!(providers.iter().all(|p| !p.fallback));
+    }
+
+    #[test]
+    fn test_section_routes() {
+        let vars = [
+            ("LLM_MODEL_OFFICE_BEARERS", "openai=gpt-4.1"),
+            ("LLM_MODEL_BALANCE_SHEET", "qwen2.5:14b"),
+            ("LLM_MODEL_SHAREHOLDERS", "llama=x"),
+            ("LLM_MODEL_OFFICEBEARERS", "openai=gpt-4.1"),
+            ("OLLAMA_MODEL", "qwen2.5:3b"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        let mut errors = Vec::new();
+        let routes = section_routes(
+            vars.into_iter(),
+            "LLM_MODEL_",
+            LlmBackend::Ollama,
+            &mut errors,
+        );
+
+        assert_eq!(routes.len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[1].starts_with("LLM_MODEL_OFFICEBEARERS: unknown section"));
+        assert!(errors[1].contains("office_bearers"));
+        assert_eq!(routes["office_bearers"].backend, LlmBackend::OpenAI);
+        assert_eq!(routes["office_bearers"].model, "gpt-4.1");
+        assert_eq!(routes["balance_sheet"].label(), "ollama qwen2.5:14b");
+    }
+
+    #[test]
+    fn test_validate() {
+        let (config, errors) = config(&[
+            ("LLM_PROVIDERS", "ollama@gpu1:11434, openai"),
+            ("OLLAMA_NUM_CTX", "65536"),
+            ("OLLAMA_SEED", "random"),
+        ]);
+
+        assert_eq!(errors, ["OLLAMA_SEED: invalid value \"random\""]);
+        let problems = config.validate();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].contains("http://"));
+        assert!(problems[1].contains("OPENAI_API_KEY"));
+        assert!(problems[2].contains("OLLAMA_MAX_CTX"));
+    }
+
+    #[test]
+    fn test_to_settings_round_trip() {
+        let (config, _) = config(&[
+            ("LLM_FALLBACK_PROVIDERS", "openai"),
+            ("OPENAI_API_KEY", "sk-test"),
+            ("OLLAMA_TEMPERATURE", ""),
+            ("LLM_MODEL_OFFICE_BEARERS", "openai=gpt-4.1"),
+        ]);
+        let mut settings = Settings::new();
+        config.to_settings(&mut settings);
+        assert_eq!(settings.get("OLLAMA_TEMPERATURE"), Some(""));
+        assert!(settings.get("OPENAI_API_KEY").is_none());
+
+        settings.set("OPENAI_API_KEY", "sk-test");
+        let mut reader = SettingsReader::new(&settings);
+        let reread = LlmConfig::from_settings(&mut reader);
+        assert!(reader.errors.is_empty(), "{:?}", reader.errors);
+        assert_eq!(reread.providers, config.providers);
+        assert_eq!(reread.ollama_temperature, None);
+        assert_eq!(reread.section_models, config.section_models);
+    }
+}
@@ -1 +1,131 @@
+//! Configuration of a run, read from a TOML file, then the environment, then
+//! command line overrides, each taking precedence over the one before.
+
 pub mod llm;
+pub mod pricing;
+pub mod processing;
+pub mod prompts;
+pub mod settings;
+
+use std::error::Error;
+use std::path::Path;
+
+use self::llm::LlmConfig;
+use self::pricing::PriceTable;
+use self::processing::ProcessingConfig;
+use self::prompts::PromptConfig;
+pub use self::settings::Settings;
+use self::settings::SettingsReader;
+
+/// Config file read when none is given, if it exists
+pub const DEFAULT_CONFIG_FILE: &str = "process-pdfs.toml";
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub llm: LlmConfig,
+    pub processing: ProcessingConfig,
+    pub prompts: PromptConfig,
+    pub prices: PriceTable,
+}
+
+impl Config {
+    /// Parse and validate settings
+    ///
+    /// # Returns
+    /// * The configuration, or an error listing every invalid setting
+    pub fn from_settings(settings: &Settings) -> Result<Self, Box<dyn Error>> {
+        let mut reader = SettingsReader::new(settings);
+        let config = Self {
+            llm: LlmConfig::from_settings(&mut reader),
+            processing: ProcessingConfig::from_settings(&mut reader),
+            prompts: PromptConfig::from_settings(&mut reader),
+            prices: PriceTable::from_settings(&mut reader),
+        };
+
+        let mut errors = reader.errors;
+        errors.extend(config.llm.validate());
+        errors.extend(config.processing.validate());
+        if !errors.is_empty() {
+            return Err(format!("Invalid configuration: {}", errors.join("; ")).into());
+        }
+        Ok(config)
+    }
+
+    /// Load the configuration of a run
+    ///
+    /// # Arguments
+    /// * `path` - TOML config file; `process-pdfs.toml` is read if `None` and it exists
+    /// * `overrides` - `KEY=VALUE` settings from the command line
+    pub fn load(path: Option<&str>, overrides: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut settings = match path {
+            Some(path) => Settings::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Settings::from_file(DEFAULT_CONFIG_FILE)?
+            }
+            None => Settings::new(),
+        };
+        settings.merge(Settings::from_env());
+        for arg in overrides {
+            let (key, value) = Settings::parse_override(arg)?;
+            settings.set(key, value);
+        }
+        Self::from_settings(&settings)
+    }
+
+    /// Every effective setting, defaults included, as a TOML config file.
+    /// The OpenAI API key is not printed.
+    pub fn to_toml(&self) -> String {
+        let mut settings = Settings::new();
+        self.llm.to_settings(&mut settings);
+        self.processing.to_settings(&mut settings);
+        self.prompts.to_settings(&mut settings);
+        self.prices.to_settings(&mut settings);
+
+        let mut toml = settings.to_toml();
+        if self.llm.openai_api_key.is_some() {
+            toml.push_str("# openai_api_key is set\n");
+        }
+        toml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_printed_config_reads_back() {
+        let settings: Settings = [
+            ("LLM_BACKEND", "openai"),
+            ("OPENAI_API_KEY", "sk-test"),
+            ("PDF_CONCURRENCY", "4"),
+            ("GROUNDING_POLICY", "clear"),
+        ]
+        .into_iter()
+        .collect();
+        let config = Config::from_settings(&settings).unwrap();
+        let toml = config.to_toml();
+        assert!(!toml.contains("sk-test"));
+
+        let mut reread = Settings::from_toml(&toml).unwrap();
+        reread.set("OPENAI_API_KEY", "sk-test");
+        let reread = Config::from_settings(&reread).unwrap();
+        assert_eq!(reread.to_toml(), toml);
+        assert_eq!(reread.processing.file_concurrency, 4);
+    }
+
+    #[test]
+    fn test_invalid_settings_are_all_reported() {
+        let settings: Settings = [
+            ("LLM_BACKEND", "llama"),
+            ("PDF_CONCURRENCY", "0"),
+            ("MIN_CONFIDENCE", "1.5"),
+        ]
+        .into_iter()
+        .collect();
+        let err = Config::from_settings(&settings).unwrap_err().to_string();
+        assert!(err.contains("LLM_BACKEND"), "{}", err);
+        assert!(err.contains("PDF_CONCURRENCY"), "{}", err);
+        assert!(err.contains("MIN_CONFIDENCE"), "{}", err);
+    }
+}
@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::config::settings::{Settings, SettingsReader};
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// Model name → price table used to estimate the cost of a run
+#[derive(Debug, Clone)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+    /// JSON file the built-in prices were overridden from
+    path: Option<String>,
+}
+
+impl PriceTable {
+    /// Built-in list prices for the OpenAI models we use. Local Ollama
+    /// models are not listed and therefore cost nothing.
+    fn builtin() -> Self {
+        let prices = [
+            ("gpt-4.1", 2.00, 8.00),
+            ("gpt-4.1-mini", 0.40, 1.60),
+            ("gpt-4.1-nano", 0.10, 0.40),
+            ("gpt-4o", 2.50, 10.00),
+            ("gpt-4o-mini", 0.15, 0.60),
+        ]
+        .into_iter()
+        .map(|(model, input, output)| (model.to_string(), ModelPrice { input, output }))
+        .collect();
+
+        Self { prices, path: None }
+    }
+
+    /// Built-in prices, overridden by the JSON file at `LLM_PRICE_TABLE` if set.
+    /// An unreadable file is recorded in `reader`.
+    ///
+    /// The file maps model names to prices, e.g.
+    /// `{"gpt-4.1-mini": {"input": 0.40, "output": 1.60}}`.
+    pub(crate) fn from_settings(reader: &mut SettingsReader) -> Self {
+        let mut table = Self::builtin();
+
+        if let Some(path) = reader.optional("LLM_PRICE_TABLE") {
+            match std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| {
+                    serde_json::from_str::<HashMap<String, ModelPrice>>(&s)
+                        .map_err(|e| e.to_string())
+                }) {
+                Ok(prices) => table.prices.extend(prices),
+                Err(e) => reader
+                    .errors
+                    .push(format!("LLM_PRICE_TABLE: cannot read {}: {}", path, e)),
+            }
+            table.path = Some(path);
+        }
+
+        table
+    }
+
+    pub(crate) fn to_settings(&self, settings: &mut Settings) {
+        settings.set("LLM_PRICE_TABLE", self.path.clone().unwrap_or_default());
+    }
+
+    /// Look up a model's price. Falls back to the longest listed prefix so
+    /// that dated snapshots (e.g. `gpt-4.1-mini-2025-04-14`) are priced too.
+    pub fn price(&self, model: &str) -> Option<ModelPrice> {
+        if let Some(price) = self.prices.get(model) {
+            return Some(*price);
+        }
+
+        self.prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// Estimated cost in USD of a call, or 0 for unknown models
+    pub fn cost(&self, model: &str, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        self.price(model).map_or(0.0, |p| {
+            (prompt_tokens as f64 * p.input + completion_tokens as f64 * p.output) / 1_000_000.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_for_known_and_unknown_models() {
+        let table = PriceTable::builtin();
+        let cost = table.cost("gpt-4.1-mini", 1_000_000, 500_000);
+        assert!((cost - 1.20).abs() < 1e-9);
+        assert_eq!(table.cost("qwen2.5:3b", 1_000, 1_000), 0.0);
+    }
+
+    #[test]
+    fn test_price_uses_longest_prefix() {
+        let table = PriceTable::builtin();
+        let price = table.price("gpt-4.1-mini-2025-04-14").unwrap();
+        assert_eq!(price.input, 0.40);
+    }
+}
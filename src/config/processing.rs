@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use crate::config::settings::{Settings, SettingsReader};
+use crate::parser::grounding::GroundingPolicy;
+
+#[derive(Debug, Clone)]
+pub struct ProcessingConfig {
+    /// Number of PDF files processed at the same time
+    pub file_concurrency: usize,
+    /// Number of sections of one file sent to the LLM at the same time
+    pub section_concurrency: usize,
+    /// Table sections longer than this many characters are extracted in chunks
+    pub chunk_max_chars: usize,
+    /// Number of table rows repeated between consecutive chunks
+    pub chunk_overlap_rows: usize,
+    /// What to do with extracted values not found in the section text
+    pub grounding_policy: GroundingPolicy,
+    /// Minimum share of a value's words that must appear in the section text
+    pub grounding_min_score: f64,
+    /// Records with a field below this confidence are added to the review queue
+    /// (0 disables the queue)
+    pub min_confidence: f64,
+    /// In watch mode, how long a file must go unchanged before it is processed
+    pub watch_debounce: Duration,
+    /// Write timing reports and markdown dumps of the extracted sections
+    pub debugging: bool,
+}
+
+impl ProcessingConfig {
+    /// Read the processing settings, recording invalid values in `reader`
+    pub(crate) fn from_settings(reader: &mut SettingsReader) -> Self {
+        let grounding_policy =
+            reader.choice("GROUNDING_POLICY", &["off", "false", "flag", "clear"]);
+        Self {
+            file_concurrency: reader.parse("PDF_CONCURRENCY", 1),
+            section_concurrency: reader.parse("SECTION_CONCURRENCY", 1),
+            chunk_max_chars: reader.parse("CHUNK_MAX_CHARS", 6000),
+            chunk_overlap_rows: reader.parse("CHUNK_OVERLAP_ROWS", 2),
+            grounding_policy: GroundingPolicy::parse(&grounding_policy),
+            grounding_min_score: reader.parse("GROUNDING_MIN_SCORE", 0.8),
+            min_confidence: reader.parse("MIN_CONFIDENCE", 0.0),
+            watch_debounce: Duration::from_millis(reader.parse("WATCH_DEBOUNCE_MS", 2000)),
+            debugging: reader.flag("DEBUGGING"),
+        }
+    }
+
+    /// Problems that make the processing settings unusable
+    pub(crate) fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (name, value) in [
+            ("PDF_CONCURRENCY", self.file_concurrency),
+            ("SECTION_CONCURRENCY", self.section_concurrency),
+            ("CHUNK_MAX_CHARS", self.chunk_max_chars),
+        ] {
+            if value == 0 {
+                errors.push(format!("{} must be positive", name));
+            }
+        }
+        for (name, value) in [
+            ("GROUNDING_MIN_SCORE", self.grounding_min_score),
+            ("MIN_CONFIDENCE", self.min_confidence),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                errors.push(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+        errors
+    }
+
+    /// The settings this configuration was read from, with defaults filled in
+    pub(crate) fn to_settings(&self, settings: &mut Settings) {
+        settings.set("PDF_CONCURRENCY", self.file_concurrency.to_string());
+        settings.set("SECTION_CONCURRENCY", self.section_concurrency.to_string());
+        settings.set("CHUNK_MAX_CHARS", self.chunk_max_chars.to_string());
+        settings.set("CHUNK_OVERLAP_ROWS", self.chunk_overlap_rows.to_string());
+        settings.set("GROUNDING_POLICY", self.grounding_policy.as_str());
+        settings.set("GROUNDING_MIN_SCORE", self.grounding_min_score.to_string());
+        settings.set("MIN_CONFIDENCE", self.min_confidence.to_string());
+        settings.set(
+            "WATCH_DEBOUNCE_MS",
+            self.watch_debounce.as_millis().to_string(),
+        );
+        settings.set("DEBUGGING", self.debugging.to_string());
+    }
+}
@@ -0,0 +1,29 @@
+use crate::config::settings::{Settings, SettingsReader};
+
+#[derive(Debug, Clone)]
+pub struct PromptConfig {
+    /// Directory whose `<name>.txt` files override the built-in prompt templates
+    pub prompt_dir: String,
+    /// Directory holding the few-shot example files
+    pub few_shot_dir: String,
+    /// Number of few-shot examples injected per prompt (0 disables them)
+    pub few_shot_max: usize,
+}
+
+impl PromptConfig {
+    /// Read the prompt settings, recording invalid values in `reader`
+    pub(crate) fn from_settings(reader: &mut SettingsReader) -> Self {
+        Self {
+            prompt_dir: reader.string("PROMPT_DIR", "prompts"),
+            few_shot_dir: reader.string("FEW_SHOT_DIR", "prompts/examples"),
+            few_shot_max: reader.parse("FEW_SHOT_MAX", 2),
+        }
+    }
+
+    /// The settings this configuration was read from, with defaults filled in
+    pub(crate) fn to_settings(&self, settings: &mut Settings) {
+        settings.set("PROMPT_DIR", &self.prompt_dir);
+        settings.set("FEW_SHOT_DIR", &self.few_shot_dir);
+        settings.set("FEW_SHOT_MAX", self.few_shot_max.to_string());
+    }
+}
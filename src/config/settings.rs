@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::str::FromStr;
+
+/// Every setting the configuration reads, named after the environment
+/// variable that sets it. Per-section routes are matched by [`SECTION_PREFIXES`].
+pub const KEYS: &[&str] = &[
+    "LLM_BACKEND",
+    "OPENAI_API_KEY",
+    "OPENAI_MODEL",
+    "OPENAI_URL",
+    "OLLAMA_MODEL",
+    "OLLAMA_URL",
+    "OLLAMA_TEMPERATURE",
+    "OLLAMA_SEED",
+    "OLLAMA_NUM_CTX",
+    "OLLAMA_MAX_CTX",
+    "OLLAMA_NUM_PREDICT",
+    "OLLAMA_KEEP_ALIVE",
+    "OLLAMA_AUTO_PULL",
+    "LOCAL_MODEL",
+    "LOCAL_TOKENIZER",
+    "LOCAL_MAX_TOKENS",
+    "LLM_PROVIDERS",
+    "LLM_FALLBACK_PROVIDERS",
+    "LLM_ROUTING",
+    "LLM_PROVIDER_MAX_FAILURES",
+    "LLM_PROVIDER_COOLDOWN",
+    "LLM_ESCALATION_MODEL",
+    "LLM_CACHE",
+    "LLM_CACHE_DIR",
+    "LLM_CASSETTE",
+    "LLM_CASSETTE_MODE",
+    "LLM_PRICE_TABLE",
+    "PDF_CONCURRENCY",
+    "SECTION_CONCURRENCY",
+    "CHUNK_MAX_CHARS",
+    "CHUNK_OVERLAP_ROWS",
+    "GROUNDING_POLICY",
+    "GROUNDING_MIN_SCORE",
+    "MIN_CONFIDENCE",
+    "WATCH_DEBOUNCE_MS",
+    "DEBUGGING",
+    "PROMPT_DIR",
+    "FEW_SHOT_DIR",
+    "FEW_SHOT_MAX",
+];
+
+/// Prefixes of the per-section model routes, e.g. `LLM_MODEL_OFFICE_BEARERS`
+pub const SECTION_PREFIXES: [&str; 2] = ["LLM_MODEL_", "LLM_ESCALATE_"];
+
+fn is_known(key: &str) -> bool {
+    KEYS.contains(&key) || SECTION_PREFIXES.iter().any(|p| key.starts_with(p))
+}
+
+/// Raw configuration values keyed by environment variable name, before
+/// they are parsed into a [`Config`](super::Config).
+///
+/// A TOML file is flattened into the same names: `[ollama] url = "..."`
+/// and `ollama_url = "..."` both set `OLLAMA_URL`, and
+/// `[llm.model] office_bearers = "..."` sets `LLM_MODEL_OFFICE_BEARERS`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    values: BTreeMap<String, String>,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Known settings from the environment, after loading `.env`
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+        std::env::vars().filter(|(key, _)| is_known(key)).collect()
+    }
+
+    /// Parse a TOML config file's content. Unknown keys are an error, so
+    /// that a misspelled setting is not silently ignored.
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        let table: toml::Table = text.parse()?;
+        let mut settings = Self::new();
+        flatten(&table, "", &mut settings.values);
+
+        let unknown: Vec<&str> = settings
+            .values
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !is_known(key))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!("Unknown settings: {}", unknown.join(", ")).into());
+        }
+        Ok(settings)
+    }
+
+    /// Read a TOML config file, see [`Settings::from_toml`]
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config file {}: {}", path, e))?;
+        Self::from_toml(&text).map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    /// Parse a command line override such as `ollama_model=qwen2.5:7b`
+    pub fn parse_override(arg: &str) -> Result<(String, String), Box<dyn Error>> {
+        let (key, value) = arg
+            .split_once('=')
+            .ok_or_else(|| format!("Expected KEY=VALUE, got {:?}", arg))?;
+        let key = key.trim().to_uppercase();
+        if !is_known(&key) {
+            return Err(format!("Unknown setting {}", key).into());
+        }
+        Ok((key, value.to_string()))
+    }
+
+    pub fn set(&mut self, key: impl AsRef<str>, value: impl Into<String>) {
+        self.values
+            .insert(key.as_ref().to_uppercase(), value.into());
+    }
+
+    /// Add every value of `other`, replacing values already set
+    pub fn merge(&mut self, other: Settings) {
+        self.values.extend(other.values);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Settings whose name starts with `prefix`
+    pub fn with_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (String, String)> + 'a {
+        self.values
+            .range(prefix.to_string()..)
+            .take_while(move |(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+    }
+
+    /// Render as a flat TOML file that [`Settings::from_toml`] reads back
+    pub fn to_toml(&self) -> String {
+        self.values
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{} = {}\n",
+                    key.to_lowercase(),
+                    toml::Value::String(value.clone())
+                )
+            })
+            .collect()
+    }
+}
+
+impl<K: AsRef<str>, V: Into<String>> FromIterator<(K, V)> for Settings {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut settings = Self::new();
+        for (key, value) in iter {
+            settings.set(key, value);
+        }
+        settings
+    }
+}
+
+/// Flatten nested tables into `PARENT_CHILD` names. Arrays become
+/// comma-separated lists, as used by `LLM_PROVIDERS`.
+fn flatten(table: &toml::Table, prefix: &str, values: &mut BTreeMap<String, String>) {
+    for (key, value) in table {
+        let name = format!("{}{}", prefix, key.to_uppercase());
+        let text = match value {
+            toml::Value::Table(table) => {
+                flatten(table, &format!("{}_", name), values);
+                continue;
+            }
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    toml::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            other => other.to_string(),
+        };
+        values.insert(name, text);
+    }
+}
+
+/// Parses settings into typed values, collecting every invalid one so that
+/// they can all be reported at once
+pub(crate) struct SettingsReader<'a> {
+    settings: &'a Settings,
+    pub errors: Vec<String>,
+}
+
+impl<'a> SettingsReader<'a> {
+    pub fn new(settings: &'a Settings) -> Self {
+        Self {
+            settings,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &'a Settings {
+        self.settings
+    }
+
+    /// A string, or `default` if unset
+    pub fn string(&self, key: &str, default: &str) -> String {
+        self.settings.get(key).unwrap_or(default).to_string()
+    }
+
+    /// A string, `None` if unset or empty
+    pub fn optional(&self, key: &str) -> Option<String> {
+        self.settings
+            .get(key)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    }
+
+    /// A value, or `default` if unset or empty
+    pub fn parse<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        match self.settings.get(key).map(str::trim) {
+            None | Some("") => default,
+            Some(value) => value.parse().unwrap_or_else(|_| {
+                self.errors
+                    .push(format!("{}: invalid value {:?}", key, value));
+                default
+            }),
+        }
+    }
+
+    /// An optional value: `default` if unset, `None` if set but empty
+    pub fn option<T: FromStr>(&mut self, key: &str, default: Option<T>) -> Option<T> {
+        match self.settings.get(key).map(str::trim) {
+            None => default,
+            Some("") => None,
+            Some(value) => {
+                let parsed = value.parse().ok();
+                if parsed.is_none() {
+                    self.errors
+                        .push(format!("{}: invalid value {:?}", key, value));
+                }
+                parsed
+            }
+        }
+    }
+
+    /// `true` or `false`, `false` if unset or empty
+    pub fn flag(&mut self, key: &str) -> bool {
+        self.parse(key, false)
+    }
+
+    /// One of `allowed` (case-insensitive), or an empty string if unset
+    pub fn choice(&mut self, key: &str, allowed: &[&str]) -> String {
+        let value = self.string(key, "").trim().to_lowercase();
+        if !value.is_empty() && !allowed.contains(&value.as_str()) {
+            self.errors.push(format!(
+                "{}: expected one of {}, got {:?}",
+                key,
+                allowed.join(", "),
+                value
+            ));
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml_flattens_tables() {
+        let settings = Settings::from_toml(
+            r#"
+            llm_backend = "openai"
+            pdf_concurrency = 4
+            debugging = true
+            llm_providers = ["ollama@http://gpu1:11434", "openai"]
+
+            [ollama]
+            url = "http://gpu:11434"
+            temperature = 0.2
+
+            [llm.model]
+            office_bearers = "openai=gpt-4.1"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.get("LLM_BACKEND"), Some("openai"));
+        assert_eq!(settings.get("PDF_CONCURRENCY"), Some("4"));
+        assert_eq!(settings.get("DEBUGGING"), Some("true"));
+        assert_eq!(
+            settings.get("LLM_PROVIDERS"),
+            Some("ollama@http://gpu1:11434,openai")
+        );
+        assert_eq!(settings.get("OLLAMA_URL"), Some("http://gpu:11434"));
+        assert_eq!(settings.get("OLLAMA_TEMPERATURE"), Some("0.2"));
+        assert_eq!(
+            settings.get("LLM_MODEL_OFFICE_BEARERS"),
+            Some("openai=gpt-4.1")
+        );
+        assert_eq!(Settings::from_toml(&settings.to_toml()).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let err = Settings::from_toml("[ollama]\nmodle = \"x\"").unwrap_err();
+        assert!(err.to_string().contains("OLLAMA_MODLE"));
+        assert!(Settings::parse_override("pdf_concurrency=2").is_ok());
+        assert!(Settings::parse_override("pdf_concurency=2").is_err());
+        assert!(Settings::parse_override("pdf_concurrency").is_err());
+    }
+
+    #[test]
+    fn test_reader_collects_invalid_values() {
+        let settings: Settings = [
+            ("PDF_CONCURRENCY", "four"),
+            ("OLLAMA_SEED", ""),
+            ("LLM_ROUTING", "random"),
+        ]
+        .into_iter()
+        .collect();
+        let mut reader = SettingsReader::new(&settings);
+
+        assert_eq!(reader.parse("PDF_CONCURRENCY", 1usize), 1);
+        assert_eq!(reader.option::<i64>("OLLAMA_SEED", Some(42)), None);
+        assert_eq!(reader.option::<i64>("OLLAMA_NUM_CTX", Some(7)), Some(7));
+        reader.choice("LLM_ROUTING", &["round-robin", "least-busy"]);
+        assert_eq!(reader.errors.len(), 2);
+    }
+}
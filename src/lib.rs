@@ -1,4 +1,5 @@
-mod config;
+pub mod config;
+pub mod mock;
 pub mod models;
 pub mod parser;
 pub mod processor;
@@ -1,6 +1,21 @@
+use clap::{Args, Parser, Subcommand};
 use std::error::Error;
+use std::io::Read;
 
-use company_pdf_viewer::processor::batch::process_pdfs_in_directory;
+use company_pdf_viewer::api::LlmBackend;
+use company_pdf_viewer::config::Config;
+use company_pdf_viewer::models::schema::{normalise_schema, SchemaProfile};
+use company_pdf_viewer::parser::cache::ResponseCache;
+use company_pdf_viewer::parser::context::LlmContext;
+use company_pdf_viewer::parser::ollama::SectionParser;
+use company_pdf_viewer::parser::section::find_section;
+use company_pdf_viewer::processor::batch::{
+    add_examples_from_output, build_markdown_for_pdf, extract_pdf_text, process_pdfs_in_directory,
+    process_single_pdf, validate_output, ProcessOptions, DEFAULT_SECTIONS,
+};
+use company_pdf_viewer::processor::batch_api::{export_batch_requests, ingest_batch_results};
+use company_pdf_viewer::processor::watch::watch_directory;
+use company_pdf_viewer::ALL_SECTIONS;
 
 use std::fs::File;
 use tracing_subscriber::EnvFilter;
@@ -17,15 +32,321 @@ pub fn init_logging(log_path: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+const INPUT_DIR: &str = "pdf";
+const OUTPUT_DIR: &str = "output_json";
+
+#[derive(Parser)]
+#[command(
+    name = "process-pdfs",
+    about = "Extract company registry PDFs into JSON files",
+    after_help = "Without a command, every PDF in 'pdf' is processed into 'output_json'."
+)]
+struct Cli {
+    /// TOML config file [default: process-pdfs.toml if it exists]
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<String>,
+
+    /// Override a setting of the config file and environment, e.g.
+    /// `--set ollama_model=qwen2.5:7b`; may be repeated
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long, global = true)]
+    print_config: bool,
+
+    /// Log file
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        default_value = "processing.log"
+    )]
+    log: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Process the PDFs of a directory into one JSON file each
+    Process(ProcessArgs),
+    /// Process the PDFs of a directory, then each new or changed one until Ctrl-C
+    Watch(ProcessArgs),
+    /// Process one PDF and print its JSON
+    One {
+        pdf: String,
+        /// Sections to extract, by index or name
+        /// [default: company_details,business_details,office_bearers]
+        #[arg(long, value_delimiter = ',', value_parser = section_arg)]
+        sections: Vec<usize>,
+    },
+    /// Print the text extracted from a PDF
+    ExtractText { pdf: String },
+    /// Print the sections a PDF is split into, as markdown
+    Sections {
+        pdf: String,
+        /// Sections to print, by index or name [default: all]
+        #[arg(long, value_delimiter = ',', value_parser = section_arg)]
+        sections: Vec<usize>,
+    },
+    /// Extract one section read from stdin and print it as JSON
+    ParseSection {
+        /// Section, by index or name, e.g. `office_bearers`
+        #[arg(value_parser = section_arg)]
+        section: usize,
+    },
+    /// Add the sections of a hand-corrected output file to the few-shot examples
+    AddExamples {
+        /// PDF the output was produced from
+        pdf: String,
+        /// Corrected output JSON file
+        corrected: String,
+        /// Sections to add, by index or name [default: every section in the file]
+        #[arg(long, value_delimiter = ',', value_parser = section_arg)]
+        sections: Vec<usize>,
+    },
+    /// Check output JSON files against the section models
+    Validate {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Print the JSON schema of a section, or of every section
+    Schema {
+        /// Section, by index or name [default: all]
+        #[arg(value_parser = section_arg)]
+        section: Option<usize>,
+        /// Print the schema as sent to this backend (ollama, openai or local)
+        #[arg(long, value_parser = backend_arg)]
+        backend: Option<LlmBackend>,
+    },
+    /// Delete every cached LLM response
+    CacheClear,
+    /// Write batch API requests for every PDF of a directory
+    BatchExport {
+        requests: String,
+        #[arg(long, short, default_value = INPUT_DIR)]
+        input: String,
+    },
+    /// Turn a batch API results file into JSON files
+    BatchIngest {
+        results: String,
+        /// Directory the requests were exported from
+        #[arg(long, short, default_value = INPUT_DIR)]
+        input: String,
+        #[arg(long, short, default_value = OUTPUT_DIR)]
+        output: String,
+    },
+}
+
+#[derive(Args)]
+struct ProcessArgs {
+    /// Directory of PDF files
+    #[arg(long, short, default_value = INPUT_DIR)]
+    input: String,
+    /// Directory the JSON files are written to
+    #[arg(long, short, default_value = OUTPUT_DIR)]
+    output: String,
+    /// Sections to extract, by index or name
+    /// [default: company_details,business_details,office_bearers]
+    #[arg(long, value_delimiter = ',', value_parser = section_arg)]
+    sections: Vec<usize>,
+    /// Only process files whose path relative to the input directory matches
+    #[arg(long, default_value = "*.pdf")]
+    glob: String,
+    /// Also process PDFs in subdirectories
+    #[arg(long, short)]
+    recursive: bool,
+    /// Process files even if their output is up to date
+    #[arg(long, short)]
+    force: bool,
+}
+
+impl ProcessArgs {
+    fn options(&self) -> ProcessOptions {
+        ProcessOptions {
+            sections: if self.sections.is_empty() {
+                DEFAULT_SECTIONS.to_vec()
+            } else {
+                self.sections.clone()
+            },
+            glob: self.glob.clone(),
+            recursive: self.recursive,
+            force: self.force,
+        }
+    }
+}
+
+fn section_arg(value: &str) -> Result<usize, String> {
+    find_section(value).ok_or_else(|| format!("unknown section {:?}", value))
+}
+
+fn backend_arg(value: &str) -> Result<LlmBackend, String> {
+    LlmBackend::parse(value).ok_or_else(|| format!("unknown backend {:?}", value))
+}
+
+/// Parser of a section, or an error naming the sections that have one
+fn parser_for(section_index: usize) -> Result<SectionParser, Box<dyn Error>> {
+    SectionParser::from_section_index(section_index).ok_or_else(|| {
+        format!(
+            "{} is not extracted; try one of: {}",
+            ALL_SECTIONS[section_index],
+            parsed_sections()
+                .map(|(_, parser)| parser.template_name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()
+    })
+}
+
+/// Every section that has a parser, with its index
+fn parsed_sections() -> impl Iterator<Item = (usize, SectionParser)> {
+    (0..ALL_SECTIONS.len()).filter_map(|i| SectionParser::from_section_index(i).map(|p| (i, p)))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+
+    // Settings come from the config file, then the environment (and .env),
+    // then --set, each overriding the one before
+    let config = Config::load(cli.config.as_deref(), &cli.overrides)?;
+    if cli.print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
+
+    init_logging(&cli.log)?;
+
+    let command = cli.command.unwrap_or(Command::Process(ProcessArgs {
+        input: INPUT_DIR.to_string(),
+        output: OUTPUT_DIR.to_string(),
+        sections: Vec::new(),
+        glob: "*.pdf".to_string(),
+        recursive: false,
+        force: false,
+    }));
 
-    init_logging("processing.log")?;
+    match command {
+        Command::Process(args) => {
+            let ctx = LlmContext::new(config)?;
+            process_pdfs_in_directory(&ctx, &args.input, &args.output, &args.options()).await?
+        }
+        Command::Watch(args) => {
+            let ctx = LlmContext::new(config)?;
+            let shutdown = async {
+                tokio::signal::ctrl_c().await.ok();
+                eprintln!("Stopping after the files in progress...");
+            };
+            watch_directory(&ctx, &args.input, &args.output, &args.options(), shutdown).await?
+        }
+        Command::One { pdf, sections } => {
+            let ctx = LlmContext::new(config)?;
+            let sections = (!sections.is_empty()).then_some(sections.as_slice());
+            let pdf_data = process_single_pdf(&ctx, &pdf, sections).await?;
+            println!("{}", serde_json::to_string_pretty(&pdf_data)?);
+        }
+        Command::ExtractText { pdf } => {
+            print!("{}", extract_pdf_text(&pdf).await?);
+        }
+        Command::Sections { pdf, sections } => {
+            let pdf_text = extract_pdf_text(&pdf).await?;
+            let sections: Vec<usize> = if sections.is_empty() {
+                (0..ALL_SECTIONS.len()).collect()
+            } else {
+                sections
+            };
+            print!("{}", build_markdown_for_pdf(&pdf, &pdf_text, &sections));
+        }
+        Command::ParseSection { section } => {
+            let parser = parser_for(section)?;
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
 
-    // Usage example: process all PDFs in the 'pdf' directory
-    // and save results to 'output_json'
-    process_pdfs_in_directory("pdf", "output_json").await?;
+            let ctx = LlmContext::new(config)?;
+            let parsed = parser
+                .parse(&ctx, &text, SectionParser::section_name(section))
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&parsed.value)?);
+        }
+        Command::AddExamples {
+            pdf,
+            corrected,
+            sections,
+        } => {
+            let corrected = serde_json::from_str(&std::fs::read_to_string(&corrected)?)?;
+            let ctx = LlmContext::new(config)?;
+            let sections = (!sections.is_empty()).then_some(sections.as_slice());
+            let count = add_examples_from_output(&ctx, &pdf, &corrected, sections)?;
+            println!(
+                "Added {} examples to {}",
+                count, ctx.config.prompts.few_shot_dir
+            );
+        }
+        Command::Validate { files } => {
+            let mut failed = 0;
+            for file in &files {
+                let problems = match std::fs::read_to_string(file)
+                    .map_err(|e| e.to_string())
+                    .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+                {
+                    Ok(data) => validate_output(&data),
+                    Err(e) => vec![e],
+                };
+                if problems.is_empty() {
+                    println!("{}: ok", file);
+                } else {
+                    failed += 1;
+                    for problem in problems {
+                        println!("{}: {}", file, problem);
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{} of {} files are invalid", failed, files.len()).into());
+            }
+        }
+        Command::Schema { section, backend } => {
+            let schema = |parser: &SectionParser| match backend {
+                Some(backend) => {
+                    normalise_schema(&parser.schema(), SchemaProfile::for_backend(&backend))
+                }
+                None => parser.schema(),
+            };
+            let output = match section {
+                Some(section) => schema(&parser_for(section)?),
+                None => parsed_sections()
+                    .map(|(_, parser)| (parser.template_name().to_string(), schema(&parser)))
+                    .collect(),
+            };
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        Command::CacheClear => {
+            let cache = ResponseCache::new(&config.llm.cache_dir, config.llm.cache_mode);
+            let count = cache.clear()?;
+            println!(
+                "Removed {} cached responses from {}",
+                count,
+                cache.dir().display()
+            );
+        }
+        Command::BatchExport { requests, input } => {
+            let ctx = LlmContext::new(config)?;
+            let count = export_batch_requests(&ctx, &input, &requests).await?;
+            println!("Wrote {} requests to {}", count, requests);
+        }
+        Command::BatchIngest {
+            results,
+            input,
+            output,
+        } => {
+            let ctx = LlmContext::new(config)?;
+            let count = ingest_batch_results(&ctx, &input, &results, &output).await?;
+            println!("Wrote {} JSON files to {}", count, output);
+        }
+    }
 
     Ok(())
 }
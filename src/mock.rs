@@ -0,0 +1,371 @@
+//! Minimal HTTP server that imitates the Ollama and OpenAI endpoints used by
+//! the parser, so that the pipeline can run without network access.
+//!
+//! Point `OLLAMA_URL` (or `OPENAI_URL`) at [`MockLlmServer::url`] and every
+//! structured output request is answered by a responder closure. The default
+//! responder returns an empty value shaped like the requested schema. The
+//! models reported by `/api/tags` and `/v1/models` are set with
+//! [`MockLlmServer::set_models`]. [`minimal_pdf`] builds PDFs to feed it.
+
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// A structured output request received by the mock server
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub path: String,
+    pub body: Value,
+}
+
+impl MockRequest {
+    /// Prompt text of the request, for either backend
+    pub fn prompt(&self) -> Option<&str> {
+        self.body
+            .pointer("/messages/0/content")
+            .or_else(|| self.body.pointer("/input/0/content/0/text"))
+            .and_then(Value::as_str)
+    }
+
+    /// JSON schema the reply must follow, for either backend
+    pub fn schema(&self) -> Option<&Value> {
+        self.body
+            .get("format")
+            .or_else(|| self.body.pointer("/response_format/json_schema/schema"))
+    }
+}
+
+type Responder = dyn Fn(&MockRequest) -> Value + Send + Sync;
+
+/// Mock LLM server listening on a random local port. Stops when dropped.
+pub struct MockLlmServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    models: Arc<Mutex<Vec<String>>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockLlmServer {
+    /// Start a server answering every request with [`sample_from_schema`]
+    pub fn start() -> std::io::Result<Self> {
+        Self::with_responder(|request| {
+            request
+                .schema()
+                .map(sample_from_schema)
+                .unwrap_or(Value::Null)
+        })
+    }
+
+    /// Start a server answering every request with the value returned by `responder`.
+    /// The value is wrapped in the reply format of the endpoint that was called.
+    pub fn with_responder(
+        responder: impl Fn(&MockRequest) -> Value + Send + Sync + 'static,
+    ) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let models = Arc::new(Mutex::new(Vec::new()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let responder: Arc<Responder> = Arc::new(responder);
+
+        let handle = {
+            let requests = Arc::clone(&requests);
+            let models = Arc::clone(&models);
+            let shutdown = Arc::clone(&shutdown);
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let requests = Arc::clone(&requests);
+                    let models = Arc::clone(&models);
+                    let responder = Arc::clone(&responder);
+                    std::thread::spawn(move || {
+                        let served =
+                            handle_connection(stream, &requests, &models, responder.as_ref());
+                        if let Err(e) = served {
+                            tracing::warn!("Mock LLM server connection failed: {}", e);
+                        }
+                    });
+                }
+            })
+        };
+
+        Ok(Self {
+            addr,
+            requests,
+            models,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:41234`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Models the server reports as available. Ollama's `/api/pull` adds to the list.
+    pub fn set_models(&self, models: &[&str]) {
+        *self.models.lock().unwrap() = models.iter().map(|m| m.to_string()).collect();
+    }
+
+    /// Structured output requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockLlmServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Read one HTTP request, answer it and close the connection
+fn handle_connection(
+    stream: TcpStream,
+    requests: &Mutex<Vec<MockRequest>>,
+    models: &Mutex<Vec<String>>,
+    responder: &Responder,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    let (status, reply) = match path.as_str() {
+        "/api/tags" => {
+            let models: Vec<Value> = models
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|name| json!({ "name": name, "model": name }))
+                .collect();
+            ("200 OK", json!({ "models": models }))
+        }
+        "/api/pull" => {
+            let name = body["model"].as_str().unwrap_or_default().to_string();
+            models.lock().unwrap().push(name);
+            ("200 OK", json!({ "status": "success" }))
+        }
+        p if p.starts_with("/v1/models/") || p.starts_with("/models/") => {
+            let name = p.rsplit('/').next().unwrap_or_default();
+            if models.lock().unwrap().iter().any(|m| m == name) {
+                ("200 OK", json!({ "id": name, "object": "model" }))
+            } else {
+                (
+                    "404 Not Found",
+                    json!({ "error": { "message": "model not found" } }),
+                )
+            }
+        }
+        "/api/chat" | "/v1/responses" | "/responses" => {
+            let request = MockRequest {
+                path: path.clone(),
+                body,
+            };
+            let content = responder(&request);
+            requests.lock().unwrap().push(request);
+
+            let reply = if path == "/api/chat" {
+                json!({
+                    "message": { "role": "assistant", "content": content.to_string() },
+                    "done": true,
+                    "prompt_eval_count": 0,
+                    "eval_count": 0,
+                })
+            } else {
+                json!({
+                    "output_parsed": content,
+                    "usage": { "input_tokens": 0, "output_tokens": 0 },
+                })
+            };
+            ("200 OK", reply)
+        }
+        _ => ("404 Not Found", json!({ "error": "not found" })),
+    };
+
+    let reply = reply.to_string();
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reply.len(),
+        reply
+    )?;
+    stream.flush()
+}
+
+/// Build the smallest value that satisfies a JSON schema: empty strings, zeros,
+/// empty lists and objects with every property present. `$ref`s into
+/// `definitions` are followed.
+pub fn sample_from_schema(schema: &Value) -> Value {
+    fn sample(node: &Value, root: &Value) -> Value {
+        if let Some(target) = node.get("$ref").and_then(Value::as_str) {
+            let pointer = target.trim_start_matches('#');
+            return root
+                .pointer(pointer)
+                .map_or(Value::Null, |resolved| sample(resolved, root));
+        }
+        if let Some(first) = ["allOf", "anyOf", "oneOf"]
+            .iter()
+            .find_map(|k| node.get(*k).and_then(Value::as_array)?.first())
+        {
+            return sample(first, root);
+        }
+
+        // Optional fields are typed `["string", "null"]`; use the first non-null type
+        let ty = match node.get("type") {
+            Some(Value::Array(types)) => types
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|t| *t != "null")
+                .unwrap_or("null"),
+            Some(Value::String(ty)) => ty.as_str(),
+            _ if node.get("properties").is_some() => "object",
+            _ => "null",
+        };
+
+        match ty {
+            "object" => {
+                let properties = node
+                    .get("properties")
+                    .and_then(Value::as_object)
+                    .map(|props| {
+                        props
+                            .iter()
+                            .map(|(key, prop)| (key.clone(), sample(prop, root)))
+                            .collect::<Map<_, _>>()
+                    })
+                    .unwrap_or_default();
+                Value::Object(properties)
+            }
+            "array" => json!([]),
+            "string" => json!(""),
+            "integer" | "number" => json!(0),
+            "boolean" => json!(false),
+            _ => Value::Null,
+        }
+    }
+
+    sample(schema, schema)
+}
+
+/// Single-page PDF with one line of Helvetica text per entry
+pub fn minimal_pdf(lines: &[&str]) -> Vec<u8> {
+    let mut content = String::from("BT\n/F1 10 Tf\n14 TL\n50 800 Td\n");
+    for line in lines {
+        let escaped = line
+            .replace('\\', "\\\\")
+            .replace('(', "\\(")
+            .replace(')', "\\)");
+        content.push_str(&format!("({escaped}) Tj T*\n"));
+    }
+    content.push_str("ET\n");
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Contents 4 0 R \
+         /Resources << /Font << /F1 5 0 R >> >> >>"
+            .to_string(),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::company::OfficeBearerList;
+    use crate::models::api::JsonSchema;
+
+    #[test]
+    fn test_sample_from_schema_follows_refs() {
+        let value = sample_from_schema(&OfficeBearerList::schema());
+        assert_eq!(value, json!({ "officeBearers": [] }));
+        assert!(serde_json::from_value::<OfficeBearerList>(value).is_ok());
+    }
+
+    #[test]
+    fn test_sample_from_schema_optional_fields() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "nickname": { "type": ["string", "null"] },
+                "inner": { "$ref": "#/definitions/Inner" }
+            },
+            "definitions": {
+                "Inner": { "type": "object", "properties": { "n": { "type": "integer" } } }
+            }
+        });
+        assert_eq!(
+            sample_from_schema(&schema),
+            json!({ "name": "", "nickname": "", "inner": { "n": 0 } })
+        );
+    }
+}
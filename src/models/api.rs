@@ -2,10 +2,91 @@ use schemars::JsonSchema as SchemarsJsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LlmBackend {
     Ollama,
     OpenAI,
+    /// GGUF model run in-process on the CPU (`local-llm` feature)
+    Local,
+}
+
+impl LlmBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LlmBackend::Ollama => "ollama",
+            LlmBackend::OpenAI => "openai",
+            LlmBackend::Local => "local",
+        }
+    }
+
+    /// Parse a backend name (`ollama`, `openai` or `local`)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "ollama" => Some(LlmBackend::Ollama),
+            "openai" => Some(LlmBackend::OpenAI),
+            "local" => Some(LlmBackend::Local),
+            _ => None,
+        }
+    }
+}
+
+/// One endpoint that can serve structured output requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmProvider {
+    pub backend: LlmBackend,
+    /// Base URL, e.g. `http://gpu1:11434` or `https://api.openai.com/v1`;
+    /// `in-process` for local models
+    pub url: String,
+    /// Model name; the path of the GGUF file for local models
+    pub model: String,
+    pub api_key: Option<String>,
+    /// Only used once every non-fallback provider has failed or is cooling down
+    pub fallback: bool,
+}
+
+impl LlmProvider {
+    /// Short name for logs, e.g. `ollama qwen2.5:3b @ http://gpu1:11434`
+    pub fn label(&self) -> String {
+        format!("{} {} @ {}", self.backend.as_str(), self.model, self.url)
+    }
+
+    /// Entry of a provider list, `backend=model@url`
+    pub fn spec(&self) -> String {
+        format!("{}={}@{}", self.backend.as_str(), self.model, self.url)
+    }
+}
+
+/// Backend and model a section is sent to instead of the providers' own model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelRoute {
+    pub backend: LlmBackend,
+    pub model: String,
+}
+
+impl ModelRoute {
+    /// Parse `backend=model` (e.g. `openai=gpt-4.1`), or a bare model name
+    /// served by `default_backend`
+    pub fn parse(value: &str, default_backend: LlmBackend) -> Option<Self> {
+        let value = value.trim();
+        let (backend, model) = match value.split_once('=') {
+            Some((backend, model)) => (LlmBackend::parse(backend)?, model.trim()),
+            None => (default_backend, value),
+        };
+        (!model.is_empty()).then(|| ModelRoute {
+            backend,
+            model: model.to_string(),
+        })
+    }
+
+    /// Short name for logs and output, e.g. `openai gpt-4.1`
+    pub fn label(&self) -> String {
+        format!("{} {}", self.backend.as_str(), self.model)
+    }
+
+    /// `backend=model`, as parsed by [`ModelRoute::parse`]
+    pub fn spec(&self) -> String {
+        format!("{}={}", self.backend.as_str(), self.model)
+    }
 }
 
 // Ollama Chat API models
@@ -15,6 +96,22 @@ pub struct OllamaChatRequest<'a> {
     pub messages: Vec<Message<'a>>,
     pub stream: bool,
     pub format: Value,
+    pub options: OllamaOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<&'a str>,
+}
+
+/// Generation options; unset values use the Ollama server defaults
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>,
 }
 
 #[derive(Serialize)]
@@ -26,6 +123,10 @@ pub struct Message<'a> {
 #[derive(Deserialize)]
 pub struct OllamaChatResponse {
     pub message: MessageResponse,
+    #[serde(default)]
+    pub prompt_eval_count: u64,
+    #[serde(default)]
+    pub eval_count: u64,
 }
 
 #[derive(Deserialize)]
@@ -62,11 +163,44 @@ pub struct OpenAIResponseFormat {
 pub struct OpenAIJsonSchema {
     pub name: String,
     pub schema: serde_json::Value,
+    /// Reject replies that do not match the schema exactly
+    pub strict: bool,
 }
 
 #[derive(Deserialize)]
 pub struct OpenAIResponse {
     pub output_parsed: Value,
+    #[serde(default)]
+    pub usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize)]
+pub struct OpenAIUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Tokens consumed by one or more LLM calls and their estimated cost in USD
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated_cost: f64,
+}
+
+impl TokenUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.estimated_cost += other.estimated_cost;
+    }
 }
 
 // Trait for schema support
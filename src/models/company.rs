@@ -4,166 +4,248 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CompanyData {
+    /// Everything known about the company
     pub organisation_info: OrganisationInfo,
+    /// Whether the full record was shown on the registry page
     pub view_all: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct OrganisationInfo {
+    /// Company Details section
     pub company_details: CompanyDetails,
+    /// Business Details section, one entry per business
     pub business_details_list: Vec<BusinessDetails>,
+    /// Particulars of Stated Capital section, one entry per class of shares
     pub stated_capitals_list: Vec<StatedCapital>,
+    /// Certificates issued by other institutions
     pub certificates_list: Vec<Certificate>,
+    /// Office Bearers section, one entry per person or company
     pub office_bearers_list: Vec<OfficeBearer>,
+    /// Shareholders section, one entry per shareholder
     pub share_holders_list: Vec<ShareHolder>,
+    /// Financial summaries or statements filed for the last 3 years
     pub financials_list: Vec<Financial>,
+    /// Liquidators section, not extracted
     pub liquidators_list: Vec<serde_json::Value>,
+    /// Annual returns filed for the last 3 years
     pub annual_return_list: Vec<AnnualReturn>,
+    /// Receivers section, not extracted
     pub receivers_list: Vec<serde_json::Value>,
+    /// Administrators section, not extracted
     pub administrators_list: Option<serde_json::Value>,
+    /// Charges section, not extracted
     pub charges_list: Vec<serde_json::Value>,
+    /// Members section (companies limited by guarantee), always empty
     pub members_list: Option<serde_json::Value>,
+    /// Winding up details section, not extracted
     pub winding_up_details_list: Vec<serde_json::Value>,
+    /// Objections section, not extracted
     pub objections_list: Vec<serde_json::Value>,
+    /// Last annual registration fee paid
     pub last_annual_registration_fee_paid: RegistrationFee,
+    /// Additional notes section, not extracted
     pub additional_notes_list: Option<serde_json::Value>,
+    /// Balance sheet of the last financial summary filed
     pub balance_sheet: super::financial::BalanceSheet,
+    /// Profit and loss statement of the last financial summary filed
     pub profit_and_loss: super::financial::ProfitAndLoss,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CompanyDetails {
+    /// Registry's internal organisation number, if printed; not the File No.
     pub org_no: String,
+    /// "File No.": a letter followed by digits, e.g. C12345
     pub org_file_no: String,
+    /// "Name" of the company, exactly as written
     pub org_name: String,
+    /// "Date Incorporated"
     pub org_incorp_date: String,
+    /// "Nature" of the company, e.g. PRIVATE or PUBLIC
     pub org_nature_cd: String,
+    /// Short code of the Nature, if printed next to it
     pub org_nature_cd_code: String,
+    /// "Type" of the company, e.g. LIMITED BY SHARES
     pub org_type_cd: String,
+    /// Current "Status" of the company, e.g. LIVE or DEFUNCT
     pub org_last_sta_cd: String,
+    /// "Registered Office Address"
     pub company_address: String,
+    /// "Category", e.g. DOMESTIC, FOREIGN(DOM BRANCH) or AUTHORISED COMPANY
     pub category_desc: String,
+    /// Short code of the Category, if printed next to it
     pub org_category_code: String,
+    /// Short code of the Sub Category, if printed next to it
     pub org_sub_category_code: Option<String>,
+    /// "Sub Category"
     pub sub_category_desc: Option<String>,
+    /// Date the company became defunct, if any
     pub defunct_date: Option<String>,
+    /// "Effective date for Registered Office Address"
     pub effective_start_date: String,
+    /// Former name of the company, if it was renamed
     pub former_org_name: String,
+    /// Total comprehensive income, if printed in this section
     pub total_comprehensive_income: String,
+    /// Winding up status, if printed in this section
     pub winding_up_status: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct BusinessDetails {
+    /// File number of the business registration, if printed
     pub bus_file_no: String,
+    /// Business registration number (BRN), if printed
     pub business_reg_no: String,
+    /// "Business Name" column; "." when the row has no name
     pub bsn_business_name: String,
+    /// Type of business, if printed
     pub business_type: Option<String>,
+    /// "Principal Place of Business" column
     pub main_address: String,
+    /// "Nature of Business" column, e.g. TRADING
     pub bus_nature: String,
+    /// Status of the business registration, if printed
     pub status: Option<String>,
+    /// Applicant name, if printed
     pub app_name: Option<String>,
+    /// Business registration date, if printed
     pub bus_reg_dt: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct BusinessDetailsList {
+    /// One entry per row of the Business Details table
     pub business_details: Vec<BusinessDetails>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct StatedCapital {
+    /// Type or class of shares, e.g. ORDINARY
     pub share_type: String,
+    /// Number of shares of this class, as written
     pub num_shares: String,
+    /// Currency of the capital, e.g. MUR or USD
     pub currency: String,
+    /// Stated capital amount for this class of shares, as written
     pub stated_capital1: String,
+    /// Par value per share, as written
     pub par_value: String,
+    /// Amount of the capital still unpaid, as written
     pub amount_unpaid: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct StatedCapitalList {
+    /// One entry per class of shares
     pub stated_capitals: Vec<StatedCapital>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Certificate {
+    /// Certificate name or number
     pub certif: String,
+    /// Type of certificate or issuing institution
     pub certif_type: String,
+    /// Date the certificate takes effect
     pub effective_date: String,
+    /// Date the certificate expires
     pub expiry_date: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CertificateList {
+    /// One entry per certificate
     pub certificates: Vec<Certificate>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct OfficeBearer {
+    /// Role only, e.g. DIRECTOR, SECRETARY or CHAIRMAN
     pub position: String,
+    /// Name of the person or company, without the position
     pub name: String,
+    /// "Service Address" column, including the country
     pub address: String,
+    /// Country of the service address (its last word)
     pub country: String,
+    /// "Appointed Date" column
     pub appointed_date: String,
+    /// Always an empty string; filled in later
     pub entity_type: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct OfficeBearerList {
+    /// One entry per row of the Office Bearers table
     pub office_bearers: Vec<OfficeBearer>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ShareHolder {
+    /// Name of the shareholder (person or company)
     pub name: String,
+    /// Number of shares held, as written
     pub num_shares: String,
+    /// Type or class of shares held, e.g. ORDINARY
     pub share_type: String,
+    /// Currency of the shares, e.g. MUR or USD
     pub currency: String,
+    /// Always an empty string; filled in later
     pub entity_type: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ShareHolderList {
+    /// One entry per row of the Shareholders table
     pub share_holders: Vec<ShareHolder>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Financial {
+    /// Date the financial year ended
     pub financial_year_ended_date: String,
+    /// Currency of the financial statements
     pub currency: String,
+    /// Date the statements were approved
     pub date_approved: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AnnualReturn {
+    /// Date of the annual return
     pub annual_return_date: String,
+    /// Date of the annual meeting
     pub annual_meeting_date: String,
+    /// Date the return was filed
     pub filed_date: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AnnualReturnList {
+    /// One entry per annual return filed
     pub annual_returns: Vec<AnnualReturn>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RegistrationFee {
+    /// Amount of the last annual registration fee paid, as written
     pub amount: String,
 }
@@ -4,89 +4,144 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct BalanceSheet {
+    /// Non-current assets
     pub non_current_assets: NonCurrentAssets,
+    /// Current assets
     pub current_assets: CurrentAssets,
+    /// Equity attributable to the owners
     pub equity_and_liabilities: EquityAndLiabilities,
+    /// Non-current liabilities
     pub non_current_liabilities: NonCurrentLiabilities,
+    /// Current liabilities and totals
     pub current_liabilities: CurrentLiabilities,
+    /// Financial year the balance sheet covers, as written
     pub financial_year: String,
+    /// Currency of the amounts, e.g. MUR
     pub currency: String,
+    /// Multiplier stated for the figures, e.g. 1000 for "Rs 000"; 1 if none is stated
     pub unit: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NonCurrentAssets {
+    /// Property, plant and equipment
     pub prop_plant_equip: i64,
+    /// Investment property
     pub invest_prop: i64,
+    /// Intangible assets
     pub intangible_assets: i64,
+    /// Investments in subsidiaries
     pub invest_in_sub: i64,
+    /// Other investments
     pub other_inv: i64,
+    /// Biological assets
     pub biological_assets: i64,
+    /// Other non-current assets
     pub others_non_current: i64,
+    /// Total non-current assets
     pub total_non_current: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CurrentAssets {
+    /// Inventories
     pub inventories: i64,
+    /// Trade and other receivables
     pub trade_and_other_recv: i64,
+    /// Cash and cash equivalents
     pub cash_and_cash_equiv: i64,
+    /// Other current assets
     pub others_current_assets: i64,
+    /// Total current assets
     pub total_current_assets: i64,
+    /// Total assets (non-current plus current)
     pub total_assets_current_assets: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EquityAndLiabilities {
+    /// Share capital
     pub share_capital: i64,
+    /// Other reserves
     pub other_reserves: i64,
+    /// Retained earnings
     pub retained_earnings: i64,
+    /// Other equity
     pub other_equi: i64,
+    /// Total equity
     pub total_equi: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NonCurrentLiabilities {
+    /// Long-term borrowings
     pub long_term_borrow: i64,
+    /// Deferred tax
     pub deferred_tax: i64,
+    /// Long-term provisions
     pub long_term_prov: i64,
+    /// Other non-current liabilities
     pub others_non_current_liab: i64,
+    /// Total non-current liabilities
     pub total_non_current_liab: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CurrentLiabilities {
+    /// Trade and other payables
     pub trade_and_other_pay: i64,
+    /// Short-term borrowings
     pub short_term_borrowings: i64,
+    /// Current tax payable
     pub current_tax_payable: i64,
+    /// Short-term provisions
     pub short_term_prov: i64,
+    /// Other current liabilities
     pub others_current_liab: i64,
+    /// Total current liabilities
     pub total_current_liab: i64,
+    /// Total liabilities
     pub total_liab: i64,
+    /// Total equity and liabilities
     pub total_equity_and_liab: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ProfitAndLoss {
+    /// Turnover or revenue
     pub turnover: i64,
+    /// Cost of sales
     pub cost_of_sales: i64,
+    /// Gross profit
     pub gross_profit: i64,
+    /// Other income
     pub other_income: i64,
+    /// Distribution costs
     pub distribution_costs: i64,
+    /// Administration costs
     pub administration_costs: i64,
+    /// Other expenses
     pub other_expenses: i64,
+    /// Finance costs
     pub finance_costs: i64,
+    /// Profit (or loss, negative) before tax
     pub profit_before_tax: i64,
+    /// Tax expense
     pub tax_expense: i64,
+    /// Profit (or loss, negative) for the period
     pub profit_for_the_period: i64,
+    /// Financial year the statement covers, as written
     pub financial_year: String,
+    /// Currency of the amounts, e.g. MUR
     pub currency: String,
+    /// Date the statements were approved
     pub approved_date: String,
+    /// Multiplier stated for the figures, e.g. 1000 for "Rs 000"; 1 if none is stated
     pub unit: i32,
 }
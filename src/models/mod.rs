@@ -1,3 +1,4 @@
 pub mod api;
 pub mod company;
 pub mod financial;
+pub mod schema;
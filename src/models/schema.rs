@@ -0,0 +1,345 @@
+use serde_json::{Map, Value};
+
+use crate::models::api::LlmBackend;
+
+/// Which subset of JSON Schema a backend's structured output accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaProfile {
+    /// Ollama turns the schema into a sampling grammar, which does not follow
+    /// `$ref`s reliably. Optional fields are left out of `required`, so the
+    /// model may omit them rather than fill them in. Also used for local models.
+    Ollama,
+    /// OpenAI strict mode: every object closed, every property required,
+    /// optional fields as nullable unions
+    OpenAI,
+}
+
+impl SchemaProfile {
+    pub fn for_backend(backend: &LlmBackend) -> Self {
+        match backend {
+            LlmBackend::Ollama | LlmBackend::Local => SchemaProfile::Ollama,
+            LlmBackend::OpenAI => SchemaProfile::OpenAI,
+        }
+    }
+}
+
+/// Keywords schemars emits that structured output backends reject or ignore
+const UNSUPPORTED_KEYWORDS: [&str; 8] = [
+    "$schema", "$id", "title", "format", "default", "minimum", "maximum", "examples",
+];
+
+/// Rewrite a schemars schema into the self-contained form structured output
+/// backends handle best:
+///
+/// * `$ref`s into `definitions` are inlined and the definitions dropped
+/// * single-entry `allOf` wrappers are flattened
+/// * unsupported keywords (`$schema`, `title`, `format`, ...) are removed
+/// * every object gets `additionalProperties: false`; optional fields stay
+///   nullable
+/// * for OpenAI, every object lists all of its properties as `required`, as
+///   strict mode demands; for Ollama, only the fields the model cannot do
+///   without are required
+/// * field descriptions (from doc comments) are kept
+///
+/// # Arguments
+/// * `schema` - Root schema as produced by `schemars::schema_for!`
+/// * `profile` - Backend the schema is sent to
+///
+/// # Returns
+/// * The rewritten schema. A recursive `$ref` is replaced by an empty schema.
+pub fn normalise_schema(schema: &Value, profile: SchemaProfile) -> Value {
+    let mut definitions = Map::new();
+    for key in ["definitions", "$defs"] {
+        if let Some(defs) = schema.get(key).and_then(Value::as_object) {
+            definitions.extend(
+                defs.iter()
+                    .map(|(k, v)| (format!("#/{key}/{k}"), v.clone())),
+            );
+        }
+    }
+
+    rewrite(schema, &definitions, profile, &mut Vec::new())
+}
+
+fn rewrite(
+    node: &Value,
+    definitions: &Map<String, Value>,
+    profile: SchemaProfile,
+    expanding: &mut Vec<String>,
+) -> Value {
+    let Value::Object(obj) = node else {
+        return node.clone();
+    };
+
+    if let Some(target) = obj.get("$ref").and_then(Value::as_str) {
+        let Some(resolved) = definitions.get(target) else {
+            return Value::Object(Map::new());
+        };
+        if expanding.iter().any(|t| t == target) {
+            return Value::Object(Map::new());
+        }
+
+        expanding.push(target.to_string());
+        let mut inlined = rewrite(resolved, definitions, profile, expanding);
+        expanding.pop();
+
+        // Keep the referring field's own description over the type's
+        if let (Some(description), Value::Object(inlined)) = (obj.get("description"), &mut inlined)
+        {
+            inlined.insert("description".to_string(), description.clone());
+        }
+        return inlined;
+    }
+
+    // schemars wraps a described `$ref` field as `{"allOf": [{"$ref": ...}], "description": ...}`
+    if let Some([only]) = obj
+        .get("allOf")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+    {
+        let mut flattened = obj.clone();
+        flattened.remove("allOf");
+        let mut inner = rewrite(only, definitions, profile, expanding);
+        if let Value::Object(inner) = &mut inner {
+            for (key, value) in flattened {
+                let value = rewrite(&value, definitions, profile, expanding);
+                inner.insert(key, value);
+            }
+            strip(inner);
+        }
+        return inner;
+    }
+
+    let mut out = Map::new();
+    for (key, value) in obj {
+        match key.as_str() {
+            "definitions" | "$defs" => {}
+            "properties" => {
+                let properties = value
+                    .as_object()
+                    .map(|props| {
+                        props
+                            .iter()
+                            .map(|(name, prop)| {
+                                (name.clone(), rewrite(prop, definitions, profile, expanding))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                out.insert(key.clone(), Value::Object(properties));
+            }
+            _ => {
+                out.insert(key.clone(), rewrite(value, definitions, profile, expanding));
+            }
+        }
+    }
+
+    if let Some(names) = out.get("properties").and_then(Value::as_object) {
+        let required = match profile {
+            SchemaProfile::OpenAI => names.keys().cloned().map(Value::String).collect(),
+            // As schemars marks them: every field that is not an `Option`
+            SchemaProfile::Ollama => obj
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter(|name| name.as_str().is_some_and(|name| names.contains_key(name)))
+                .cloned()
+                .collect(),
+        };
+        out.insert("required".to_string(), Value::Array(required));
+        out.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+
+    strip(&mut out);
+    Value::Object(out)
+}
+
+fn strip(obj: &mut Map<String, Value>) {
+    for keyword in UNSUPPORTED_KEYWORDS {
+        obj.remove(keyword);
+    }
+}
+
+/// Describe every documented field of a schema, one line per field, e.g.
+/// `- officeBearers[].position: Role only, e.g. DIRECTOR`. Used to explain
+/// the fields in the prompt for backends that do not read schema descriptions.
+///
+/// # Arguments
+/// * `schema` - Schema as returned by [`normalise_schema`]
+pub fn field_guide(schema: &Value) -> String {
+    fn walk(node: &Value, prefix: &str, out: &mut Vec<String>) {
+        if let Some(items) = node.get("items") {
+            return walk(items, &format!("{prefix}[]"), out);
+        }
+        let Some(properties) = node.get("properties").and_then(Value::as_object) else {
+            return;
+        };
+        for (name, prop) in properties {
+            let path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{prefix}.{name}")
+            };
+            if let Some(description) = prop.get("description").and_then(Value::as_str) {
+                out.push(format!("- {path}: {description}"));
+            }
+            walk(prop, &path, out);
+        }
+    }
+
+    let mut lines = Vec::new();
+    walk(schema, "", &mut lines);
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::company::{CompanyDetails, OfficeBearerList};
+    use crate::financial::BalanceSheet;
+    use crate::models::api::JsonSchema;
+    use serde_json::json;
+
+    fn contains_key(value: &Value, key: &str) -> bool {
+        match value {
+            Value::Object(obj) => {
+                obj.contains_key(key) || obj.values().any(|v| contains_key(v, key))
+            }
+            Value::Array(items) => items.iter().any(|v| contains_key(v, key)),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_refs_are_inlined() {
+        let schema = normalise_schema(&OfficeBearerList::schema(), SchemaProfile::Ollama);
+
+        for key in ["$ref", "definitions", "$schema", "title"] {
+            assert!(!contains_key(&schema, key), "{key} left in schema");
+        }
+        let bearer = &schema["properties"]["officeBearers"]["items"];
+        assert_eq!(bearer["type"], "object");
+        assert_eq!(bearer["additionalProperties"], false);
+        assert_eq!(bearer["required"].as_array().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_optional_fields_are_required_and_nullable() {
+        let schema = normalise_schema(&CompanyDetails::schema(), SchemaProfile::OpenAI);
+        let required = schema["required"].as_array().unwrap();
+
+        assert!(required.contains(&json!("windingUpStatus")));
+        assert_eq!(
+            schema["properties"]["windingUpStatus"]["type"],
+            json!(["string", "null"])
+        );
+    }
+
+    #[test]
+    fn test_ollama_leaves_optional_fields_out_of_required() {
+        let openai = normalise_schema(&CompanyDetails::schema(), SchemaProfile::OpenAI);
+        let ollama = normalise_schema(&CompanyDetails::schema(), SchemaProfile::Ollama);
+        assert_ne!(openai, ollama);
+
+        let required = ollama["required"].as_array().unwrap();
+        assert!(!required.contains(&json!("windingUpStatus")));
+        assert!(required.len() < openai["required"].as_array().unwrap().len());
+        // Still nullable, and still closed
+        assert_eq!(
+            ollama["properties"]["windingUpStatus"]["type"],
+            json!(["string", "null"])
+        );
+        assert_eq!(ollama["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_nested_objects_and_formats() {
+        let schema = normalise_schema(&BalanceSheet::schema(), SchemaProfile::Ollama);
+
+        assert!(!contains_key(&schema, "format"));
+        let assets = &schema["properties"]["currentAssets"];
+        assert_eq!(assets["additionalProperties"], false);
+        assert_eq!(
+            assets["properties"]["inventories"],
+            json!({"type": "integer", "description": "Inventories"})
+        );
+    }
+
+    #[test]
+    fn test_field_descriptions_are_kept() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "inner": { "allOf": [{ "$ref": "#/definitions/Inner" }], "description": "Nested" }
+            },
+            "definitions": {
+                "Inner": { "type": "object", "description": "Inner type", "properties": {} }
+            }
+        });
+
+        let openai = normalise_schema(&schema, SchemaProfile::OpenAI);
+        assert_eq!(openai["properties"]["inner"]["description"], "Nested");
+        assert_eq!(openai["properties"]["inner"]["type"], "object");
+
+        let ollama = normalise_schema(&schema, SchemaProfile::Ollama);
+        assert_eq!(ollama["properties"]["inner"]["description"], "Nested");
+    }
+
+    #[test]
+    fn test_field_guide() {
+        let schema = normalise_schema(&OfficeBearerList::schema(), SchemaProfile::Ollama);
+        let guide = field_guide(&schema);
+
+        assert!(guide.starts_with("- officeBearers: "));
+        assert!(guide.contains("\n- officeBearers[].position: Role only"));
+        assert_eq!(guide.lines().count(), 7);
+    }
+
+    #[test]
+    fn test_every_model_field_is_documented() {
+        fn undocumented(node: &Value, path: &str, out: &mut Vec<String>) {
+            let node = node.get("items").unwrap_or(node);
+            for (name, prop) in node
+                .get("properties")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+            {
+                let path = format!("{path}/{name}");
+                if prop.get("description").is_none() {
+                    out.push(path.clone());
+                }
+                undocumented(prop, &path, out);
+            }
+        }
+
+        let mut missing = Vec::new();
+        undocumented(
+            &normalise_schema(
+                &crate::company::CompanyData::schema(),
+                SchemaProfile::OpenAI,
+            ),
+            "",
+            &mut missing,
+        );
+        assert!(missing.is_empty(), "undocumented fields: {missing:?}");
+    }
+
+    #[test]
+    fn test_recursive_ref_is_cut() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "node": { "$ref": "#/definitions/Node" } },
+            "definitions": {
+                "Node": { "type": "object", "properties": { "next": { "$ref": "#/definitions/Node" } } }
+            }
+        });
+
+        let normalised = normalise_schema(&schema, SchemaProfile::OpenAI);
+        assert_eq!(
+            normalised["properties"]["node"]["properties"]["next"],
+            json!({})
+        );
+    }
+}
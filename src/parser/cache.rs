@@ -0,0 +1,247 @@
+use crate::processor::atomic::write_atomic;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// How the response cache is consulted for a provider call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Serve hits from disk and store misses
+    ReadWrite,
+    /// Never read or write the cache
+    Bypass,
+    /// Ignore existing entries and overwrite them with fresh responses
+    Refresh,
+}
+
+impl CacheMode {
+    /// Parse the `LLM_CACHE` setting (`on`, `off` or `refresh`)
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "off" | "false" | "bypass" => CacheMode::Bypass,
+            "refresh" | "evict" => CacheMode::Refresh,
+            _ => CacheMode::ReadWrite,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheMode::ReadWrite => "on",
+            CacheMode::Bypass => "off",
+            CacheMode::Refresh => "refresh",
+        }
+    }
+}
+
+/// Content-addressed on-disk cache of structured LLM responses.
+///
+/// Entries live at `<dir>/<first two hex chars>/<sha256>.json` and hold the
+/// parsed JSON content returned by the provider.
+pub struct ResponseCache {
+    dir: PathBuf,
+    mode: CacheMode,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>, mode: CacheMode) -> Self {
+        Self {
+            dir: dir.into(),
+            mode,
+        }
+    }
+
+    /// Compute the cache key for a provider call
+    ///
+    /// # Arguments
+    /// * `backend` - Backend name (e.g. `ollama`)
+    /// * `model` - Model identifier sent to the backend
+    /// * `options` - Generation options that affect the reply (temperature, seed, ...)
+    /// * `prompt` - Full prompt text
+    /// * `schema` - JSON schema sent as the structured output format
+    ///
+    /// # Returns
+    /// * Lowercase hex SHA-256 digest
+    pub fn key(
+        backend: &str,
+        model: &str,
+        options: &Value,
+        prompt: &str,
+        schema: &Value,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            backend,
+            model,
+            &options.to_string(),
+            prompt,
+            &schema.to_string(),
+        ] {
+            // Length-prefix every part so that boundaries cannot collide
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{key}.json"))
+    }
+
+    /// Look up a cached response. Always misses unless the mode is `ReadWrite`.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        if self.mode != CacheMode::ReadWrite {
+            return None;
+        }
+
+        let bytes = std::fs::read(self.entry_path(key)).ok()?;
+        match serde_json::from_slice(&bytes) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::warn!("Discarding corrupt cache entry {}: {}", key, e);
+                self.evict(key);
+                None
+            }
+        }
+    }
+
+    /// Store a response. Does nothing in `Bypass` mode.
+    pub fn put(&self, key: &str, value: &Value) -> std::io::Result<()> {
+        if self.mode == CacheMode::Bypass {
+            return Ok(());
+        }
+
+        let path = self.entry_path(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Concurrent sections may store the same entry; each writes its own
+        // temp file, so readers never see a partial entry
+        write_atomic(path, serde_json::to_vec(value)?)
+    }
+
+    /// Remove a single entry, ignoring missing files
+    pub fn evict(&self, key: &str) {
+        let _ = std::fs::remove_file(self.entry_path(key));
+    }
+
+    /// Remove every entry from the cache directory
+    ///
+    /// Only the entry subdirectories are removed, so other files in a
+    /// misconfigured `LLM_CACHE_DIR` are left alone.
+    ///
+    /// # Returns
+    /// * Number of entries removed
+    pub fn clear(&self) -> std::io::Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            let is_prefix = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit()));
+            if is_prefix && path.is_dir() {
+                removed += count_entries(&path)?;
+                std::fs::remove_dir_all(&path)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+fn count_entries(dir: &Path) -> std::io::Result<usize> {
+    let mut count = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            count += count_entries(&path)?;
+        } else if path.extension().is_some_and(|e| e == "json") {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_key_is_stable_and_input_sensitive() {
+        let schema = json!({"type": "object"});
+        let options = json!({"temperature": 0.0});
+        let a = ResponseCache::key("ollama", "qwen2.5:3b", &options, "prompt", &schema);
+        let b = ResponseCache::key("ollama", "qwen2.5:3b", &options, "prompt", &schema);
+        let c = ResponseCache::key("openai", "qwen2.5:3b", &options, "prompt", &schema);
+        let d = ResponseCache::key("ollama", "qwen2.5:3bp", &options, "rompt", &schema);
+        let e = ResponseCache::key("ollama", "qwen2.5:3b", &json!(null), "prompt", &schema);
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, d);
+        assert_ne!(a, e);
+        assert_eq!(a.len(), 64);
+    }
+
+    #[test]
+    fn test_roundtrip_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::new(dir.path().join("cache"), CacheMode::ReadWrite);
+        let key = ResponseCache::key("ollama", "m", &json!(null), "p", &json!({}));
+
+        assert!(cache.get(&key).is_none());
+        cache.put(&key, &json!({"orgName": "ACME LTD"})).unwrap();
+        assert_eq!(cache.get(&key), Some(json!({"orgName": "ACME LTD"})));
+
+        let other = dir.path().join("cache").join("notes.txt");
+        std::fs::write(&other, "kept").unwrap();
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(cache.get(&key).is_none());
+        assert!(other.exists());
+        assert_eq!(cache.clear().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_put_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::new(dir.path(), CacheMode::ReadWrite);
+        let key = ResponseCache::key("ollama", "m", &json!(null), "p", &json!({}));
+
+        cache.put(&key, &json!(1)).unwrap();
+        cache.put(&key, &json!(2)).unwrap();
+        let files: Vec<_> = std::fs::read_dir(dir.path().join(&key[..2]))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(files, [format!("{key}.json")]);
+        assert_eq!(cache.get(&key), Some(json!(2)));
+    }
+
+    #[test]
+    fn test_bypass_and_refresh_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = ResponseCache::key("ollama", "m", &json!(null), "p", &json!({}));
+
+        let bypass = ResponseCache::new(dir.path(), CacheMode::Bypass);
+        bypass.put(&key, &json!(1)).unwrap();
+        assert!(!dir.path().join(&key[..2]).exists());
+
+        let refresh = ResponseCache::new(dir.path(), CacheMode::Refresh);
+        refresh.put(&key, &json!(2)).unwrap();
+        assert!(refresh.get(&key).is_none());
+
+        let read_write = ResponseCache::new(dir.path(), CacheMode::ReadWrite);
+        assert_eq!(read_write.get(&key), Some(json!(2)));
+    }
+}
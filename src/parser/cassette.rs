@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Whether provider calls are recorded to, or replayed from, a cassette file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Off,
+    /// Send requests to the backend and append every exchange to the cassette
+    Record,
+    /// Answer requests from the cassette only; a missing entry is an error
+    Replay,
+}
+
+impl CassetteMode {
+    /// Parse the `LLM_CASSETTE_MODE` setting (`record`, `replay` or `off`)
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "record" => CassetteMode::Record,
+            "replay" => CassetteMode::Replay,
+            _ => CassetteMode::Off,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CassetteMode::Off => "off",
+            CassetteMode::Record => "record",
+            CassetteMode::Replay => "replay",
+        }
+    }
+}
+
+/// One recorded request/response exchange
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    /// Same key as the response cache: hash of backend, model, options, prompt and schema
+    pub key: String,
+    /// Request body as sent to the backend
+    pub request: Value,
+    /// Raw response body as returned by the backend
+    pub response: Value,
+}
+
+/// JSONL file of recorded backend exchanges, used to run the pipeline
+/// without network access
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    entries: Mutex<HashMap<String, Value>>,
+}
+
+impl Cassette {
+    /// Open a cassette, loading its existing entries
+    pub fn open(path: impl Into<PathBuf>, mode: CassetteMode) -> std::io::Result<Self> {
+        let path = path.into();
+        let mut entries = HashMap::new();
+
+        if path.exists() {
+            for (i, line) in std::fs::read_to_string(&path)?.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry: CassetteEntry = serde_json::from_str(line).map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{}:{}: {}", path.display(), i + 1, e),
+                    )
+                })?;
+                entries.insert(entry.key, entry.response);
+            }
+        } else if mode == CassetteMode::Replay {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("cassette {} does not exist", path.display()),
+            ));
+        }
+
+        Ok(Self {
+            path,
+            mode,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Recorded response for a request key
+    pub fn replay(&self, key: &str) -> Option<Value> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    /// Append an exchange to the cassette. Keys already recorded are skipped.
+    pub fn record(&self, key: &str, request: &Value, response: &Value) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(key) {
+            return Ok(());
+        }
+
+        let entry = CassetteEntry {
+            key: key.to_string(),
+            request: request.clone(),
+            response: response.clone(),
+        };
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+        entries.insert(entry.key, entry.response);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassettes/run.jsonl");
+
+        let recorder = Cassette::open(&path, CassetteMode::Record).unwrap();
+        recorder
+            .record(
+                "k1",
+                &json!({"model": "m"}),
+                &json!({"message": {"content": "{}"}}),
+            )
+            .unwrap();
+        recorder
+            .record("k1", &json!({"model": "m"}), &json!({"ignored": true}))
+            .unwrap();
+
+        let player = Cassette::open(&path, CassetteMode::Replay).unwrap();
+        assert_eq!(
+            player.replay("k1"),
+            Some(json!({"message": {"content": "{}"}}))
+        );
+        assert!(player.replay("k2").is_none());
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn test_replay_requires_existing_cassette() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Cassette::open(dir.path().join("missing.jsonl"), CassetteMode::Replay).is_err());
+    }
+}
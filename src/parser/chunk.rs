@@ -0,0 +1,374 @@
+use serde_json::Value;
+
+/// Positions that start a new row in the Office Bearers table
+const OFFICE_BEARER_POSITIONS: [&str; 9] = [
+    "DIRECTOR",
+    "ALTERNATE DIRECTOR",
+    "MANAGING DIRECTOR",
+    "SECRETARY",
+    "CHAIRMAN",
+    "CHAIRPERSON",
+    "MANAGER",
+    "CHIEF EXECUTIVE OFFICER",
+    "AUDITOR",
+];
+
+/// How to split a table section into rows and tell its rows apart
+pub struct TableLayout {
+    /// Whether a line starts a new table row
+    pub is_row_start: fn(&str) -> bool,
+    /// Fields identifying a row when merging chunk results
+    pub key_fields: &'static [&'static str],
+}
+
+/// Whether a line of the Office Bearers section starts a new table row
+pub fn is_office_bearer_row(line: &str) -> bool {
+    let line = line.trim_start().to_uppercase();
+    OFFICE_BEARER_POSITIONS
+        .iter()
+        .any(|position| line.starts_with(position))
+}
+
+/// Whether a line of the Shareholders section starts a new table row
+///
+/// Cells are top-aligned, so the first line of a row holds the shareholder's
+/// name followed by the number of shares, the share type and usually the
+/// currency, e.g. `DOE JOHN 1,000 ORDINARY MUR`. Lines a long name wraps
+/// onto carry no share count. A count must follow some name and be followed
+/// by a word, which leaves out headers and page numbers such as `Page 2 of 3`.
+pub fn is_shareholder_row(line: &str) -> bool {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let is_count = |token: &str| {
+        token.chars().any(|c| c.is_ascii_digit())
+            && token
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == ',' || c == '.')
+    };
+    let Some(last) = tokens.last() else {
+        return false;
+    };
+    tokens.len() >= 3
+        && last.chars().all(char::is_alphabetic)
+        && tokens[1..tokens.len() - 1]
+            .iter()
+            .any(|token| is_count(token))
+}
+
+/// A chunk of a table section
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// Number of leading rows repeated from the end of the previous chunk
+    pub overlap: usize,
+}
+
+/// Split a table section into overlapping chunks that never cut a row in half.
+///
+/// Lines before the first row (section title, column headers) are repeated
+/// at the top of every chunk so that each chunk reads like the full table.
+///
+/// # Arguments
+/// * `section` - Section text, starting with its header line
+/// * `is_row_start` - Whether a line starts a new table row
+/// * `max_chars` - Target size of a chunk; a single oversized row still gets its own chunk
+/// * `overlap_rows` - Number of trailing rows of a chunk repeated at the start of the next
+///
+/// # Returns
+/// * The chunks, or a single chunk holding the whole section if it fits
+pub fn split_rows(
+    section: &str,
+    is_row_start: fn(&str) -> bool,
+    max_chars: usize,
+    overlap_rows: usize,
+) -> Vec<Chunk> {
+    let whole = || Chunk {
+        text: section.to_string(),
+        overlap: 0,
+    };
+    if section.len() <= max_chars {
+        return vec![whole()];
+    }
+
+    let mut lines = section.lines();
+    let mut preamble = String::new();
+    if let Some(title) = lines.next() {
+        preamble.push_str(title);
+        preamble.push('\n');
+    }
+
+    // Group the remaining lines into rows; lines before the first row start
+    // belong to the preamble
+    let mut rows: Vec<String> = Vec::new();
+    for line in lines {
+        if is_row_start(line) {
+            rows.push(format!("{line}\n"));
+        } else if let Some(row) = rows.last_mut() {
+            row.push_str(line);
+            row.push('\n');
+        } else {
+            preamble.push_str(line);
+            preamble.push('\n');
+        }
+    }
+
+    let budget = max_chars.saturating_sub(preamble.len()).max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut overlap = 0;
+
+    while start < rows.len() {
+        let mut end = start;
+        let mut size = 0;
+        while end < rows.len() && (end == start || size + rows[end].len() <= budget) {
+            size += rows[end].len();
+            end += 1;
+        }
+
+        let mut chunk = preamble.clone();
+        for row in &rows[start..end] {
+            chunk.push_str(row);
+        }
+        chunks.push(Chunk {
+            text: chunk.trim_end().to_string(),
+            overlap,
+        });
+
+        if end == rows.len() {
+            break;
+        }
+        // Step back for the overlap, but always make progress
+        start = end.saturating_sub(overlap_rows).max(start + 1);
+        overlap = end - start;
+    }
+
+    if chunks.is_empty() {
+        chunks.push(whole());
+    }
+    chunks
+}
+
+/// Normalised identity of a row, built from its key fields, or `None` if
+/// they are all empty
+fn row_key(row: &Value, key_fields: &[&str]) -> Option<String> {
+    let parts: Vec<String> = key_fields
+        .iter()
+        .map(|field| {
+            row.get(field)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_uppercase()
+        })
+        .collect();
+    if parts.iter().all(String::is_empty) {
+        return None;
+    }
+    Some(parts.join("|"))
+}
+
+/// Amount of non-empty text in a row, used to prefer complete copies of a
+/// row over copies cut at a chunk boundary
+fn row_weight(row: &Value) -> usize {
+    match row {
+        Value::Object(obj) => obj.values().filter_map(Value::as_str).map(str::len).sum(),
+        _ => 0,
+    }
+}
+
+/// Merge rows extracted from overlapping chunks into a single list.
+///
+/// Only the overlap is de-duplicated: each of the first `overlaps[i]` rows of
+/// part `i` is matched on its key fields against the last `overlaps[i]` rows
+/// of part `i - 1`, keeping the most complete copy. Other rows are kept even
+/// if their key repeats, e.g. two holdings of the same shareholder, and so are
+/// rows whose key fields are all empty.
+///
+/// # Arguments
+/// * `parts` - Rows extracted from each chunk, in order
+/// * `overlaps` - Number of rows each chunk repeats from the previous one, see [`Chunk::overlap`]
+/// * `key_fields` - Fields identifying a row
+///
+/// # Returns
+/// * The merged rows and, for each of them, how many chunks produced it
+pub fn merge_rows(
+    parts: Vec<Vec<Value>>,
+    overlaps: &[usize],
+    key_fields: &[&str],
+) -> (Vec<Value>, Vec<usize>) {
+    let mut merged: Vec<Value> = Vec::new();
+    let mut seen_count: Vec<usize> = Vec::new();
+    // Position in `merged` of each row of the previous part
+    let mut previous: Vec<usize> = Vec::new();
+
+    for (part, &overlap) in parts.into_iter().zip(overlaps) {
+        // Rows of the previous part that may appear again at the top of this one
+        let mut window = previous[previous.len().saturating_sub(overlap)..].to_vec();
+        let mut positions = Vec::with_capacity(part.len());
+
+        for (i, row) in part.into_iter().enumerate() {
+            let matched = match row_key(&row, key_fields) {
+                Some(key) if i < overlap => window
+                    .iter()
+                    .position(|&m| row_key(&merged[m], key_fields).as_ref() == Some(&key)),
+                _ => None,
+            };
+            match matched {
+                Some(w) => {
+                    let m = window.remove(w);
+                    seen_count[m] += 1;
+                    if row_weight(&row) > row_weight(&merged[m]) {
+                        merged[m] = row;
+                    }
+                    positions.push(m);
+                }
+                None => {
+                    positions.push(merged.len());
+                    merged.push(row);
+                    seen_count.push(1);
+                }
+            }
+        }
+        previous = positions;
+    }
+
+    (merged, seen_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bearers_section(n: usize) -> String {
+        let mut text =
+            String::from("Office Bearers\nPosition Name Service Address Appointed Date\n");
+        for i in 0..n {
+            text.push_str(&format!(
+                "DIRECTOR PERSON {i}\nROYAL ROAD PORT LOUIS MAURITIUS 01/01/2020\n"
+            ));
+        }
+        text
+    }
+
+    #[test]
+    fn test_short_section_is_not_split() {
+        let section = bearers_section(2);
+        assert_eq!(
+            split_rows(&section, is_office_bearer_row, 10_000, 1),
+            vec![Chunk {
+                text: section,
+                overlap: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_chunks_keep_header_rows_and_overlap() {
+        let section = bearers_section(10);
+        let chunks: Vec<String> = split_rows(&section, is_office_bearer_row, 300, 1)
+            .into_iter()
+            .map(|chunk| chunk.text)
+            .collect();
+        assert!(chunks.len() > 1);
+
+        for chunk in &chunks {
+            assert!(chunk.starts_with("Office Bearers\nPosition Name"));
+            // Every row is complete: each DIRECTOR line is followed by its address
+            assert_eq!(
+                chunk.matches("DIRECTOR").count(),
+                chunk.matches("MAURITIUS").count()
+            );
+        }
+
+        // The last row of a chunk opens the next one
+        let last_of_first = chunks[0].lines().rev().nth(1).unwrap();
+        assert!(chunks[1].contains(last_of_first));
+
+        // Every row appears somewhere
+        for i in 0..10 {
+            assert!(chunks.iter().any(|c| c.contains(&format!("PERSON {i}\n"))));
+        }
+    }
+
+    #[test]
+    fn test_merge_rows_dedupes_and_keeps_most_complete() {
+        let a = json!({"position": "DIRECTOR", "name": "JOHN  DOE", "appointedDate": "01/01/2020", "address": ""});
+        let a_full = json!({"position": "DIRECTOR", "name": "John Doe", "appointedDate": "01/01/2020", "address": "PORT LOUIS"});
+        let b = json!({"position": "SECRETARY", "name": "JANE DOE", "appointedDate": "", "address": ""});
+
+        let (merged, counts) = merge_rows(
+            vec![vec![b.clone(), a], vec![a_full.clone()]],
+            &[0, 1],
+            &["position", "name", "appointedDate"],
+        );
+
+        assert_eq!(merged, vec![b, a_full]);
+        assert_eq!(counts, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_merge_rows_keeps_repeated_keys_outside_the_overlap() {
+        let holding = json!({"name": "DOE JOHN", "shareType": "ORDINARY", "numShares": "100"});
+        let blank = json!({"name": "", "shareType": "", "numShares": ""});
+        let other = json!({"name": "ACME LTD", "shareType": "ORDINARY", "numShares": "5"});
+        let key_fields = ["name", "shareType", "numShares"];
+
+        // Two identical holdings in one chunk are two rows
+        let (merged, _) = merge_rows(
+            vec![vec![holding.clone(), holding.clone(), blank.clone()]],
+            &[0],
+            &key_fields,
+        );
+        assert_eq!(merged.len(), 3);
+
+        // The same holding in both chunks, but outside the overlap
+        let (merged, _) = merge_rows(
+            vec![
+                vec![holding.clone(), other.clone()],
+                vec![other.clone(), holding.clone()],
+            ],
+            &[0, 1],
+            &key_fields,
+        );
+        assert_eq!(merged, vec![holding.clone(), other, holding]);
+
+        // Rows without a key are never merged
+        let (merged, _) = merge_rows(vec![vec![blank.clone()], vec![blank]], &[0, 1], &key_fields);
+        assert_eq!(merged.len(), 2);
+    }
+
+    #[test]
+    fn test_shareholder_rows_start_at_the_share_count() {
+        assert!(is_shareholder_row("DOE JOHN 1,000 ORDINARY MUR"));
+        assert!(is_shareholder_row(
+            "ACME HOLDINGS (MAURITIUS) LTD 250000 ORDINARY"
+        ));
+        assert!(!is_shareholder_row("INTERNATIONAL LIMITED"));
+        assert!(!is_shareholder_row(
+            "Name No. of Shares Type of Shares Currency"
+        ));
+        assert!(!is_shareholder_row("Page 2 of 3"));
+        assert!(!is_shareholder_row(""));
+
+        let mut section =
+            String::from("Shareholders\nName No. of Shares Type of Shares Currency\n");
+        for i in 0..10 {
+            section.push_str(&format!(
+                "HOLDER {i} INVESTMENTS 1,000 ORDINARY MUR\nINTERNATIONAL LIMITED\n"
+            ));
+        }
+        let chunks = split_rows(&section, is_shareholder_row, 250, 1);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].overlap, 0);
+        assert!(chunks[1..].iter().all(|chunk| chunk.overlap == 1));
+        for chunk in &chunks {
+            // Wrapped names stay with their row
+            assert_eq!(
+                chunk.text.matches("ORDINARY").count(),
+                chunk.text.matches("INTERNATIONAL LIMITED").count()
+            );
+        }
+    }
+}
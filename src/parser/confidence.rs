@@ -0,0 +1,201 @@
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::parser::grounding::{leaves, GroundingSource};
+use crate::parser::validation::invalid_fields;
+
+/// How a section was extracted
+#[derive(Debug, Clone)]
+pub enum ExtractionStrategy {
+    /// The whole section in one prompt
+    Single,
+    /// Overlapping chunks merged into one list. Holds, for each merged row,
+    /// how many chunks extracted it.
+    Chunked { row_sightings: Vec<usize> },
+}
+
+impl ExtractionStrategy {
+    /// Starting confidence before grounding and validation are applied.
+    /// Chunked rows may have been cut at a boundary, so they start lower.
+    fn base(&self) -> f64 {
+        match self {
+            ExtractionStrategy::Single => 0.9,
+            ExtractionStrategy::Chunked { .. } => 0.85,
+        }
+    }
+
+    /// Whether more than one extraction agreed on the row holding `path`
+    fn agrees(&self, list_key: Option<&str>, path: &str) -> bool {
+        let (ExtractionStrategy::Chunked { row_sightings }, Some(list_key)) = (self, list_key)
+        else {
+            return false;
+        };
+
+        path.strip_prefix(&format!("/{list_key}/"))
+            .and_then(|rest| rest.split('/').next())
+            .and_then(|row| row.parse::<usize>().ok())
+            .and_then(|row| row_sightings.get(row))
+            .is_some_and(|&seen| seen > 1)
+    }
+}
+
+/// Confidence of every field of an extracted section, between 0 and 1.
+///
+/// Starts from the strategy's base confidence, multiplied by the field's
+/// grounding score (share of its words found in the section; amounts must
+/// appear as printed) and halved if the field fails its format rule. Rows
+/// extracted identically by several overlapping chunks get a 0.1 bonus.
+///
+/// # Arguments
+/// * `value` - Extracted section, in model shape, before grounding clears anything
+/// * `source` - Section text it was extracted from
+/// * `strategy` - How the section was extracted
+/// * `list_key` - Key of the row list for table sections
+///
+/// # Returns
+/// * JSON pointer → confidence, rounded to two decimals
+pub fn field_confidence(
+    value: &Value,
+    source: &str,
+    strategy: &ExtractionStrategy,
+    list_key: Option<&str>,
+) -> BTreeMap<String, f64> {
+    let source = GroundingSource::new(source);
+    let invalid: HashSet<String> = invalid_fields(value).into_iter().collect();
+
+    leaves(value)
+        .into_iter()
+        .filter_map(|(path, leaf)| {
+            let grounding = match leaf {
+                Value::String(s) => source.score(s),
+                Value::Number(n) => match n.as_i64() {
+                    Some(n) if source.has_number(n) => 1.0,
+                    _ => 0.5,
+                },
+                _ => return None,
+            };
+
+            let mut confidence = strategy.base() * grounding;
+            if invalid.contains(&path) {
+                confidence *= 0.5;
+            }
+            if strategy.agrees(list_key, &path) {
+                confidence = (confidence + 0.1).min(1.0);
+            }
+
+            Some((path, (confidence * 100.0).round() / 100.0))
+        })
+        .collect()
+}
+
+/// A record (company details, or one table row) whose weakest field is below
+/// the review threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct LowConfidenceRecord {
+    /// JSON pointer of the record within the section
+    pub pointer: String,
+    pub min_confidence: f64,
+}
+
+/// Find the records of a section whose weakest field is below `min_confidence`
+///
+/// # Arguments
+/// * `confidence` - Field confidences from [`field_confidence`]
+/// * `list_key` - Key of the row list for table sections; other sections form a single record
+pub fn low_confidence_records(
+    confidence: &BTreeMap<String, f64>,
+    list_key: Option<&str>,
+    min_confidence: f64,
+) -> Vec<LowConfidenceRecord> {
+    let mut records: BTreeMap<(usize, String), f64> = BTreeMap::new();
+
+    for (path, &score) in confidence {
+        // Rows are keyed by index so that they sort numerically
+        let pointer = list_key
+            .and_then(|key| {
+                let rest = path.strip_prefix(&format!("/{key}/"))?;
+                let row = rest.split('/').next()?;
+                Some((row.parse::<usize>().ok()?, format!("/{key}/{row}")))
+            })
+            .unwrap_or_default();
+
+        let entry = records.entry(pointer).or_insert(f64::MAX);
+        *entry = entry.min(score);
+    }
+
+    records
+        .into_iter()
+        .filter(|(_, min)| *min < min_confidence)
+        .map(|((_, pointer), min_confidence)| LowConfidenceRecord {
+            pointer,
+            min_confidence,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SOURCE: &str = "Office Bearers\nDIRECTOR JOHN DOE PORT LOUIS 01/01/2020\nSECRETARY JANE ROE CUREPIPE 02/02/2021";
+
+    #[test]
+    fn test_confidence_combines_grounding_and_validation() {
+        let value = json!({"officeBearers": [
+            {"name": "JOHN DOE", "appointedDate": "01/01/2020"},
+            {"name": "MARY SMITH", "appointedDate": "CUREPIPE"}
+        ]});
+
+        let confidence = field_confidence(
+            &value,
+            SOURCE,
+            &ExtractionStrategy::Single,
+            Some("officeBearers"),
+        );
+        assert_eq!(confidence["/officeBearers/0/name"], 0.9);
+        assert_eq!(confidence["/officeBearers/1/name"], 0.0);
+        // Grounded but not a date
+        assert_eq!(confidence["/officeBearers/1/appointedDate"], 0.45);
+    }
+
+    #[test]
+    fn test_chunk_agreement_raises_confidence() {
+        let value = json!({"officeBearers": [{"name": "JOHN DOE"}, {"name": "JANE ROE"}]});
+        let strategy = ExtractionStrategy::Chunked {
+            row_sightings: vec![2, 1],
+        };
+
+        let confidence = field_confidence(&value, SOURCE, &strategy, Some("officeBearers"));
+        assert_eq!(confidence["/officeBearers/0/name"], 0.95);
+        assert_eq!(confidence["/officeBearers/1/name"], 0.85);
+    }
+
+    #[test]
+    fn test_low_confidence_records() {
+        let confidence = BTreeMap::from([
+            ("/officeBearers/0/name".to_string(), 0.9),
+            ("/officeBearers/1/name".to_string(), 0.2),
+            ("/officeBearers/10/name".to_string(), 0.3),
+            ("/officeBearers/1/address".to_string(), 0.9),
+        ]);
+
+        let records = low_confidence_records(&confidence, Some("officeBearers"), 0.5);
+        assert_eq!(
+            records,
+            vec![
+                LowConfidenceRecord {
+                    pointer: "/officeBearers/1".to_string(),
+                    min_confidence: 0.2
+                },
+                LowConfidenceRecord {
+                    pointer: "/officeBearers/10".to_string(),
+                    min_confidence: 0.3
+                },
+            ]
+        );
+
+        let details = BTreeMap::from([("/orgName".to_string(), 0.4)]);
+        assert_eq!(low_confidence_records(&details, None, 0.5)[0].pointer, "");
+    }
+}
@@ -0,0 +1,355 @@
+use serde_json::Value;
+
+/// How far a piece of text gets through a JSON schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    /// The text cannot be part of a matching value
+    Fail,
+    /// The text is a valid start but the value is unfinished
+    More,
+    /// The text is a complete matching value
+    Done,
+}
+
+/// Continuation called with the position where a parsed value ends
+type Next<'a> = &'a mut dyn FnMut(usize) -> Step;
+
+/// Restricts generated text to JSON matching a schema, for backends that
+/// decode token by token without a grammar of their own.
+///
+/// The schema is expected in the form returned by `normalise_schema`: no
+/// `$ref`s. Objects are generated with all of their properties, required or
+/// not, in schema order and no whitespace between tokens, which keeps the
+/// grammar deterministic. An empty schema accepts any scalar.
+pub struct JsonConstraint {
+    schema: Value,
+}
+
+impl JsonConstraint {
+    pub fn new(schema: &Value) -> Self {
+        Self {
+            schema: schema.clone(),
+        }
+    }
+
+    fn check(&self, text: &str) -> Step {
+        let chars: Vec<char> = text.chars().collect();
+        let end = chars.len();
+        value(&self.schema, &chars, 0, &mut |pos| {
+            if pos == end {
+                Step::Done
+            } else {
+                Step::Fail
+            }
+        })
+    }
+
+    /// Whether `text` can still be extended into a value matching the schema
+    pub fn allows(&self, text: &str) -> bool {
+        self.check(text) != Step::Fail
+    }
+
+    /// Whether `text` is a complete value matching the schema
+    pub fn is_complete(&self, text: &str) -> bool {
+        self.check(text) == Step::Done
+    }
+
+    /// Pick the next token of a constrained generation
+    ///
+    /// # Arguments
+    /// * `text` - Text generated so far
+    /// * `ranked` - Candidate token ids, most likely first
+    /// * `token_text` - Text of a token id, `None` for tokens that cannot be
+    ///   generated (special tokens, partial UTF-8 sequences)
+    /// * `eos` - End-of-sequence token id
+    ///
+    /// # Returns
+    /// * `eos` once `text` is complete, otherwise the most likely token that
+    ///   keeps the text valid, or `None` if no candidate does
+    pub fn next_token<'a>(
+        &self,
+        text: &str,
+        ranked: impl IntoIterator<Item = u32>,
+        token_text: impl Fn(u32) -> Option<&'a str>,
+        eos: u32,
+    ) -> Option<u32> {
+        if self.is_complete(text) {
+            return Some(eos);
+        }
+        ranked.into_iter().find(|&id| {
+            id != eos
+                && token_text(id)
+                    .is_some_and(|t| !t.is_empty() && self.allows(&format!("{text}{t}")))
+        })
+    }
+}
+
+fn value(schema: &Value, s: &[char], pos: usize, next: Next) -> Step {
+    for key in ["anyOf", "oneOf"] {
+        if let Some(options) = schema.get(key).and_then(Value::as_array) {
+            return options
+                .iter()
+                .map(|option| value(option, s, pos, next))
+                .max()
+                .unwrap_or(Step::Fail);
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        return options
+            .iter()
+            .map(|option| literal(&option.to_string(), s, pos, next))
+            .max()
+            .unwrap_or(Step::Fail);
+    }
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(ty)) => vec![ty.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        _ if schema.get("properties").is_some() => vec!["object"],
+        _ => vec!["string", "number", "boolean", "null"],
+    };
+
+    let any = Value::Null;
+    types
+        .into_iter()
+        .map(|ty| match ty {
+            "object" => {
+                let properties: Vec<(&String, &Value)> = schema
+                    .get("properties")
+                    .and_then(Value::as_object)
+                    .map(|props| props.iter().collect())
+                    .unwrap_or_default();
+                literal("{", s, pos, &mut |p| members(&properties, 0, s, p, next))
+            }
+            "array" => {
+                let items = schema.get("items").unwrap_or(&any);
+                literal("[", s, pos, &mut |p| {
+                    let empty = literal("]", s, p, next);
+                    empty.max(elements(items, s, p, next))
+                })
+            }
+            "string" => string(s, pos, next),
+            "integer" => number(true, s, pos, next),
+            "number" => number(false, s, pos, next),
+            "boolean" => {
+                let t = literal("true", s, pos, next);
+                t.max(literal("false", s, pos, next))
+            }
+            "null" => literal("null", s, pos, next),
+            _ => Step::Fail,
+        })
+        .max()
+        .unwrap_or(Step::Fail)
+}
+
+/// Object members from `index` on, then the closing brace
+fn members(
+    properties: &[(&String, &Value)],
+    index: usize,
+    s: &[char],
+    pos: usize,
+    next: Next,
+) -> Step {
+    let Some((name, schema)) = properties.get(index) else {
+        return literal("}", s, pos, next);
+    };
+    let separator = if index == 0 { "" } else { "," };
+    let key = format!("{separator}{}:", Value::String(name.to_string()));
+    literal(&key, s, pos, &mut |p| {
+        value(schema, s, p, &mut |p| {
+            members(properties, index + 1, s, p, next)
+        })
+    })
+}
+
+/// Array elements, each followed by a comma or the closing bracket
+fn elements(items: &Value, s: &[char], pos: usize, next: Next) -> Step {
+    value(items, s, pos, &mut |p| {
+        let close = literal("]", s, p, next);
+        close.max(literal(",", s, p, &mut |p| elements(items, s, p, next)))
+    })
+}
+
+fn literal(expected: &str, s: &[char], pos: usize, next: Next) -> Step {
+    let mut p = pos;
+    for c in expected.chars() {
+        match s.get(p) {
+            None => return Step::More,
+            Some(&actual) if actual == c => p += 1,
+            Some(_) => return Step::Fail,
+        }
+    }
+    next(p)
+}
+
+fn string(s: &[char], pos: usize, next: Next) -> Step {
+    match s.get(pos) {
+        None => return Step::More,
+        Some('"') => {}
+        Some(_) => return Step::Fail,
+    }
+
+    let mut p = pos + 1;
+    loop {
+        match s.get(p) {
+            None => return Step::More,
+            Some('"') => return next(p + 1),
+            Some('\\') => match s.get(p + 1) {
+                None => return Step::More,
+                Some('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') => p += 2,
+                Some('u') => {
+                    for i in 0..4 {
+                        match s.get(p + 2 + i) {
+                            None => return Step::More,
+                            Some(c) if c.is_ascii_hexdigit() => {}
+                            Some(_) => return Step::Fail,
+                        }
+                    }
+                    p += 6;
+                }
+                Some(_) => return Step::Fail,
+            },
+            Some(&c) if (c as u32) < 0x20 => return Step::Fail,
+            Some(_) => p += 1,
+        }
+    }
+}
+
+fn number(integer: bool, s: &[char], pos: usize, next: Next) -> Step {
+    let digits = |mut p: usize| {
+        while s.get(p).is_some_and(char::is_ascii_digit) {
+            p += 1;
+        }
+        p
+    };
+    // A number can always go on at the end of the text
+    let end = |p: usize, next: Next| {
+        if p == s.len() {
+            Step::More.max(next(p))
+        } else {
+            next(p)
+        }
+    };
+
+    let mut p = pos;
+    if s.get(p) == Some(&'-') {
+        p += 1;
+    }
+    p = match s.get(p) {
+        None => return Step::More,
+        Some('0') => p + 1,
+        Some(c) if c.is_ascii_digit() => digits(p),
+        Some(_) => return Step::Fail,
+    };
+    if integer {
+        return end(p, next);
+    }
+
+    if s.get(p) == Some(&'.') {
+        match s.get(p + 1) {
+            None => return Step::More,
+            Some(c) if c.is_ascii_digit() => p = digits(p + 1),
+            Some(_) => return Step::Fail,
+        }
+    }
+    if matches!(s.get(p), Some('e' | 'E')) {
+        p += 1;
+        if matches!(s.get(p), Some('+' | '-')) {
+            p += 1;
+        }
+        match s.get(p) {
+            None => return Step::More,
+            Some(c) if c.is_ascii_digit() => p = digits(p),
+            Some(_) => return Step::Fail,
+        }
+    }
+    end(p, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::company::OfficeBearerList;
+    use crate::models::api::JsonSchema;
+    use crate::models::schema::{normalise_schema, SchemaProfile};
+    use serde_json::json;
+
+    fn constraint() -> JsonConstraint {
+        JsonConstraint::new(&json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "shares": { "type": "integer" },
+                "ratio": { "type": "number" },
+                "note": { "type": ["string", "null"] },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            }
+        }))
+    }
+
+    #[test]
+    fn test_prefixes_of_a_matching_value_are_allowed() {
+        let c = constraint();
+        let text = r#"{"name":"A \"B\"","note":null,"ratio":-1.5e3,"shares":120,"tags":["x","y"]}"#;
+
+        for end in 0..text.len() {
+            assert!(c.allows(&text[..end]), "prefix rejected: {}", &text[..end]);
+            assert!(!c.is_complete(&text[..end]));
+        }
+        assert!(c.is_complete(text));
+        assert!(!c.allows(&format!("{text} ")));
+    }
+
+    #[test]
+    fn test_invalid_text_is_rejected() {
+        let c = constraint();
+        assert!(!c.allows("```json"));
+        assert!(!c.allows(r#"{"shares""#));
+        assert!(!c.allows(r#"{"name":12"#));
+        assert!(!c.allows(r#"{"name":"A","note":null,"ratio":1,"shares":1.5"#));
+        assert!(!c.allows(r#"{"name":"A","note":null,"ratio":1,"shares":01"#));
+        assert!(!c.allows("{\"name\":\"line\nbreak"));
+        assert!(c.allows(r#"{"name":"A","note":"n"#));
+        assert!(c.allows(r#"{"name":"A","note":"x","ratio":0,"shares":1,"tags":[]}"#));
+    }
+
+    #[test]
+    fn test_next_token_follows_the_schema() {
+        let schema = normalise_schema(&OfficeBearerList::schema(), SchemaProfile::Ollama);
+        let c = JsonConstraint::new(&schema);
+
+        // A toy vocabulary whose most likely token is always prose
+        let vocab = [
+            "Sure",
+            "<eos>",
+            "}",
+            "]",
+            "{",
+            "[",
+            "\"",
+            ",",
+            ":",
+            "officeBearers",
+            "DOE",
+        ];
+        let eos = 1;
+        let mut text = String::new();
+        for _ in 0..20 {
+            let token = c
+                .next_token(
+                    &text,
+                    0..vocab.len() as u32,
+                    |id| vocab.get(id as usize).copied(),
+                    eos,
+                )
+                .unwrap();
+            if token == eos {
+                break;
+            }
+            text.push_str(vocab[token as usize]);
+        }
+
+        assert_eq!(text, r#"{"officeBearers":[]}"#);
+        assert!(c.is_complete(&text));
+    }
+}
@@ -0,0 +1,62 @@
+use reqwest::Client;
+use std::error::Error;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::parser::cassette::{Cassette, CassetteMode};
+use crate::parser::examples::ExampleBank;
+use crate::parser::prompts::PromptLibrary;
+use crate::parser::router::ProviderRouter;
+
+/// What a run needs to send requests to the LLM providers: its
+/// configuration, prompt templates and few-shot examples, an HTTP client,
+/// the provider router and the cassette.
+///
+/// Provider health, prompts and examples are held per context, so two
+/// contexts in one process do not affect each other.
+pub struct LlmContext {
+    pub config: Config,
+    pub client: Client,
+    pub router: ProviderRouter,
+    pub prompts: PromptLibrary,
+    pub examples: ExampleBank,
+    cassette: Option<Cassette>,
+}
+
+impl LlmContext {
+    /// Set up a run, loading the prompt templates from `PROMPT_DIR` and the
+    /// examples from `FEW_SHOT_DIR`, and opening the cassette if
+    /// `LLM_CASSETTE_MODE` enables one.
+    /// An unreadable cassette is an error rather than a silent fallback to the network.
+    pub fn new(config: Config) -> Result<Self, Box<dyn Error>> {
+        let cassette = match config.llm.cassette_mode {
+            CassetteMode::Off => None,
+            mode => Some(
+                Cassette::open(&config.llm.cassette_path, mode).map_err(|e| {
+                    format!(
+                        "Could not open LLM cassette {}: {}",
+                        config.llm.cassette_path, e
+                    )
+                })?,
+            ),
+        };
+
+        Ok(Self {
+            client: Client::new(),
+            router: ProviderRouter::new(
+                config.llm.providers.clone(),
+                config.llm.routing,
+                config.llm.provider_max_failures,
+                config.llm.provider_cooldown,
+            ),
+            prompts: PromptLibrary::load(Path::new(&config.prompts.prompt_dir)),
+            examples: ExampleBank::load(&config.prompts.few_shot_dir, config.prompts.few_shot_max),
+            cassette,
+            config,
+        })
+    }
+
+    pub fn cassette(&self) -> Option<&Cassette> {
+        self.cassette.as_ref()
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A worked input/output pair shown to the model before the real section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FewShotExample {
+    /// Section text as extracted from the PDF
+    pub input: String,
+    /// Expected structured output for that text
+    pub output: Value,
+}
+
+/// Per-section store of few-shot examples.
+///
+/// Examples live in `<dir>/<template name>.jsonl`, one example per line, so
+/// that corrected outputs can simply be appended to the file.
+pub struct ExampleBank {
+    dir: PathBuf,
+    max_examples: usize,
+    examples: HashMap<String, Vec<FewShotExample>>,
+}
+
+impl ExampleBank {
+    /// Load every `*.jsonl` file in `dir`. Malformed lines are logged and skipped.
+    ///
+    /// # Arguments
+    /// * `dir` - Directory holding the example files
+    /// * `max_examples` - Number of examples injected per prompt (the most recent ones)
+    pub fn load(dir: impl Into<PathBuf>, max_examples: usize) -> Self {
+        let dir = dir.into();
+        let mut examples = HashMap::new();
+
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+                if path.extension().is_none_or(|e| e != "jsonl") {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                examples.insert(name.to_string(), read_examples(&path));
+            }
+        }
+
+        Self {
+            dir,
+            max_examples,
+            examples,
+        }
+    }
+
+    /// Examples to inject for a section, newest last
+    pub fn for_section(&self, template_name: &str) -> &[FewShotExample] {
+        let all = self
+            .examples
+            .get(template_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        &all[all.len().saturating_sub(self.max_examples)..]
+    }
+
+    /// Append an example to a section's file
+    pub fn append(&self, template_name: &str, example: &FewShotExample) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(format!("{template_name}.jsonl")))?;
+        writeln!(file, "{}", serde_json::to_string(example)?)
+    }
+}
+
+fn read_examples(path: &Path) -> Vec<FewShotExample> {
+    let Ok(source) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| match serde_json::from_str(line) {
+            Ok(example) => Some(example),
+            Err(e) => {
+                tracing::warn!("Ignoring {}:{}: {}", path.display(), i + 1, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Render examples as a prompt block
+///
+/// # Returns
+/// * The block (empty if there are no examples) and a short content hash
+///   identifying it, used to version the prompt
+pub fn render_examples(examples: &[FewShotExample]) -> (String, Option<String>) {
+    if examples.is_empty() {
+        return (String::new(), None);
+    }
+
+    let mut block = String::from("EXAMPLES:\n");
+    for (i, example) in examples.iter().enumerate() {
+        block.push_str(&format!(
+            "\nExample {n} input:\n{input}\n\nExample {n} output:\n{output}\n",
+            n = i + 1,
+            input = example.input.trim(),
+            output = example.output,
+        ));
+    }
+
+    let hash = format!("{:x}", Sha256::digest(block.as_bytes()));
+    (block, Some(hash[..8].to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn example(name: &str) -> FewShotExample {
+        FewShotExample {
+            input: format!("Office Bearers\nDIRECTOR {name}"),
+            output: json!({"officeBearers": [{"position": "DIRECTOR", "name": name}]}),
+        }
+    }
+
+    #[test]
+    fn test_append_and_select_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let bank = ExampleBank::load(dir.path(), 2);
+        for name in ["A", "B", "C"] {
+            bank.append("office_bearers", &example(name)).unwrap();
+        }
+
+        let bank = ExampleBank::load(dir.path(), 2);
+        let selected = bank.for_section("office_bearers");
+        assert_eq!(selected.len(), 2);
+        assert!(selected[0].input.ends_with('B'));
+        assert!(selected[1].input.ends_with('C'));
+        assert!(bank.for_section("shareholders").is_empty());
+    }
+
+    #[test]
+    fn test_render_examples() {
+        assert_eq!(render_examples(&[]), (String::new(), None));
+
+        let (block, hash) = render_examples(&[example("A")]);
+        assert!(block.contains("Example 1 input:\nOffice Bearers\nDIRECTOR A"));
+        assert!(block.contains(r#""name":"A""#));
+        assert_eq!(hash.unwrap().len(), 8);
+    }
+}
@@ -0,0 +1,282 @@
+use serde_json::Value;
+use std::collections::HashSet;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// What to do with extracted values that cannot be found in the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroundingPolicy {
+    /// Do not check values
+    Off,
+    /// Keep values but report them as ungrounded
+    Flag,
+    /// Replace values with an empty string and report them
+    Clear,
+}
+
+impl GroundingPolicy {
+    /// Parse the `GROUNDING_POLICY` setting (`off`, `flag` or `clear`)
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "off" | "false" => GroundingPolicy::Off,
+            "clear" => GroundingPolicy::Clear,
+            _ => GroundingPolicy::Flag,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroundingPolicy::Off => "off",
+            GroundingPolicy::Flag => "flag",
+            GroundingPolicy::Clear => "clear",
+        }
+    }
+}
+
+/// Strip accents, uppercase, turn punctuation into spaces and collapse
+/// whitespace, so that `"Royal Rd., Port-Louis"` and `"ROYAL RD PORT LOUIS"`
+/// compare equal, and so do `"Hélène"` and `"HELENE"`
+pub fn normalise(text: &str) -> String {
+    text.nfd()
+        .filter(|&c| !is_combining_mark(c))
+        .flat_map(|c| {
+            let c = if c.is_alphanumeric() { c } else { ' ' };
+            c.to_uppercase()
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Levenshtein distance, bailing out early once it exceeds `max`
+fn edit_distance_within(a: &str, b: &str, max: usize) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return false;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        if row.iter().min().copied().unwrap_or(0) > max {
+            return false;
+        }
+        prev = row;
+    }
+    prev[b.len()] <= max
+}
+
+/// Normalised source text prepared for repeated lookups
+pub struct GroundingSource {
+    text: String,
+    tokens: HashSet<String>,
+    numbers: HashSet<i64>,
+}
+
+impl GroundingSource {
+    pub fn new(source: &str) -> Self {
+        let text = normalise(source);
+        let tokens = text.split(' ').map(str::to_string).collect();
+
+        // Amounts as printed in statements: "1,250", "(1,250)", "-1250"
+        let numbers = source
+            .split_whitespace()
+            .filter_map(|word| {
+                let negative = word.starts_with('(') || word.starts_with('-');
+                let digits: String = word.chars().filter(|c| c.is_ascii_digit()).collect();
+                let only_amount = word
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || "(),.-".contains(c));
+                if digits.is_empty() || !only_amount {
+                    return None;
+                }
+                let n: i64 = digits.parse().ok()?;
+                Some(if negative { -n } else { n })
+            })
+            .collect();
+
+        Self {
+            text,
+            tokens,
+            numbers,
+        }
+    }
+
+    /// Whether an extracted amount is printed in the source. Zero counts as
+    /// found since missing amounts are extracted as 0.
+    pub fn has_number(&self, n: i64) -> bool {
+        n == 0 || self.numbers.contains(&n) || self.numbers.contains(&-n)
+    }
+
+    fn has_token(&self, token: &str) -> bool {
+        if self.tokens.contains(token) {
+            return true;
+        }
+        // Allow one typo in longer words (OCR noise, model misspellings)
+        token.chars().count() >= 5
+            && self
+                .tokens
+                .iter()
+                .any(|candidate| edit_distance_within(token, candidate, 1))
+    }
+
+    /// Share of the value's words found in the source, between 0 and 1.
+    /// Values without any word (empty, ".") score 1.
+    pub fn score(&self, value: &str) -> f64 {
+        let value = normalise(value);
+        if value.is_empty() || self.text.contains(&value) {
+            return 1.0;
+        }
+
+        let tokens: Vec<&str> = value.split(' ').collect();
+        let found = tokens.iter().filter(|t| self.has_token(t)).count();
+        found as f64 / tokens.len() as f64
+    }
+}
+
+/// Every scalar leaf of a value with its JSON pointer, in document order
+pub fn leaves(value: &Value) -> Vec<(String, &Value)> {
+    fn walk<'a>(value: &'a Value, path: String, out: &mut Vec<(String, &'a Value)>) {
+        match value {
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    walk(item, format!("{path}/{i}"), out);
+                }
+            }
+            Value::Object(obj) => {
+                for (key, item) in obj {
+                    walk(item, format!("{path}/{key}"), out);
+                }
+            }
+            leaf => out.push((path, leaf)),
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(value, String::new(), &mut out);
+    out
+}
+
+/// Check every string in an extracted value against its source section.
+///
+/// # Arguments
+/// * `value` - Extracted section, in model shape
+/// * `source` - Section text it was extracted from
+/// * `policy` - `Clear` empties ungrounded strings in place
+/// * `min_score` - Minimum share of a value's words that must appear in the source
+///
+/// # Returns
+/// * JSON pointers (e.g. `/officeBearers/3/name`) of the ungrounded strings
+pub fn check_grounding(
+    value: &mut Value,
+    source: &str,
+    policy: GroundingPolicy,
+    min_score: f64,
+) -> Vec<String> {
+    if policy == GroundingPolicy::Off {
+        return Vec::new();
+    }
+
+    let source = GroundingSource::new(source);
+    let ungrounded: Vec<String> = leaves(value)
+        .into_iter()
+        .filter_map(|(path, leaf)| leaf.as_str().map(|s| (path, s)))
+        .filter(|(_, s)| source.score(s) < min_score)
+        .map(|(path, _)| path)
+        .collect();
+
+    if policy == GroundingPolicy::Clear {
+        for path in &ungrounded {
+            if let Some(leaf) = value.pointer_mut(path) {
+                *leaf = Value::String(String::new());
+            }
+        }
+    }
+
+    ungrounded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SOURCE: &str =
+        "Office Bearers\nDIRECTOR BEDEUX JEAN ALAIN\nRoyal Rd., Port-Louis MAURITIUS 01/02/2003";
+
+    #[test]
+    fn test_normalise() {
+        assert_eq!(normalise(" Royal Rd., Port-Louis "), "ROYAL RD PORT LOUIS");
+        assert_eq!(normalise("Hélène Bérenger-Noël"), "HELENE BERENGER NOEL");
+        assert_eq!(normalise("HÉLÈNE"), normalise("hélène"));
+    }
+
+    #[test]
+    fn test_accented_names_are_grounded() {
+        let source = "Office Bearers\nDIRECTOR BÉRENGER Hélène\nRue de l'Église, Curepipe";
+        let extracted = json!({"officeBearers": [
+            {"name": "BERENGER HÉLÈNE", "address": "RUE DE L'ÉGLISE CUREPIPE"}
+        ]});
+
+        let mut cleared = extracted.clone();
+        let paths = check_grounding(&mut cleared, source, GroundingPolicy::Clear, 0.8);
+        assert!(paths.is_empty(), "{:?}", paths);
+        assert_eq!(cleared, extracted);
+    }
+
+    #[test]
+    fn test_score() {
+        let source = GroundingSource::new(SOURCE);
+        assert_eq!(source.score("royal rd port louis"), 1.0);
+        assert_eq!(source.score("ALAIN JEAN BEDEUX"), 1.0);
+        assert_eq!(source.score("BEDEAUX JEAN ALAIN"), 1.0);
+        assert_eq!(source.score(""), 1.0);
+        assert_eq!(source.score("JOHN SMITH"), 0.0);
+    }
+
+    #[test]
+    fn test_flag_and_clear() {
+        let extracted = json!({"officeBearers": [
+            {"name": "BEDEUX JEAN ALAIN", "address": "ROYAL RD PORT LOUIS", "country": "MAURITIUS"},
+            {"name": "JOHN SMITH", "address": "", "country": "FRANCE"}
+        ]});
+
+        let mut flagged = extracted.clone();
+        let paths = check_grounding(&mut flagged, SOURCE, GroundingPolicy::Flag, 0.8);
+        assert_eq!(
+            paths,
+            vec!["/officeBearers/1/country", "/officeBearers/1/name"]
+        );
+        assert_eq!(flagged, extracted);
+
+        let mut cleared = extracted.clone();
+        check_grounding(&mut cleared, SOURCE, GroundingPolicy::Clear, 0.8);
+        assert_eq!(cleared["officeBearers"][1]["name"], "");
+        assert_eq!(cleared["officeBearers"][0]["name"], "BEDEUX JEAN ALAIN");
+
+        let mut untouched = extracted.clone();
+        assert!(check_grounding(&mut untouched, SOURCE, GroundingPolicy::Off, 0.8).is_empty());
+    }
+
+    #[test]
+    fn test_has_number() {
+        let source = GroundingSource::new("Turnover 1,250,000\nTax (3,400)");
+        assert!(source.has_number(1_250_000));
+        assert!(source.has_number(-3_400));
+        assert!(source.has_number(0));
+        assert!(!source.has_number(999));
+    }
+
+    #[test]
+    fn test_leaves() {
+        let value = json!({"a": [{"b": "x"}, {"b": 2}], "c": null});
+        let paths: Vec<String> = leaves(&value).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, vec!["/a/0/b", "/a/1/b", "/c"]);
+    }
+}
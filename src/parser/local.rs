@@ -0,0 +1,144 @@
+//! In-process inference with a quantized Qwen2 GGUF model on the CPU, built
+//! with the `local-llm` feature. Output is constrained to the requested
+//! schema by [`JsonConstraint`], so every reply is valid JSON of the right shape.
+
+use candle_core::quantized::gguf_file;
+use candle_core::{Device, Tensor};
+use candle_transformers::models::quantized_qwen2::ModelWeights;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::sync::{Arc, Mutex};
+use tokenizers::Tokenizer;
+
+use crate::parser::constrained::JsonConstraint;
+
+/// A loaded model. Requests to the same model run one at a time.
+pub struct LocalModel {
+    weights: Mutex<ModelWeights>,
+    tokenizer: Tokenizer,
+    /// Text of every token id, `None` for special tokens and for tokens that
+    /// are not whole UTF-8 characters on their own
+    vocab: Vec<Option<String>>,
+    eos: u32,
+}
+
+impl LocalModel {
+    /// Load a GGUF model and its `tokenizer.json`
+    pub fn load(model_path: &str, tokenizer_path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(model_path)
+            .map_err(|e| format!("Cannot open GGUF model {}: {}", model_path, e))?;
+        let content = gguf_file::Content::read(&mut file)?;
+        let weights = ModelWeights::from_gguf(content, &mut file, &Device::Cpu)?;
+
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| format!("Cannot load tokenizer {}: {}", tokenizer_path, e))?;
+        let eos = tokenizer
+            .token_to_id("<|im_end|>")
+            .ok_or("Tokenizer has no <|im_end|> token; only Qwen2 chat models are supported")?;
+
+        let special: HashSet<u32> = tokenizer.get_added_tokens_decoder().into_keys().collect();
+        let vocab = (0..tokenizer.get_vocab_size(true) as u32)
+            .map(|id| {
+                if special.contains(&id) {
+                    return None;
+                }
+                tokenizer
+                    .decode(&[id], false)
+                    .ok()
+                    .filter(|text| !text.is_empty() && !text.contains('\u{FFFD}'))
+            })
+            .collect();
+
+        tracing::info!("Loaded local model {}", model_path);
+        Ok(Self {
+            weights: Mutex::new(weights),
+            tokenizer,
+            vocab,
+            eos,
+        })
+    }
+
+    /// Greedily generate a reply to `prompt` that matches `schema`
+    ///
+    /// # Arguments
+    /// * `prompt` - User message, wrapped in the Qwen chat template
+    /// * `schema` - Normalised schema of the reply
+    /// * `max_tokens` - Generation fails if the reply is not complete by then
+    ///
+    /// # Returns
+    /// * The reply and its prompt and completion token counts
+    pub fn generate(
+        &self,
+        prompt: &str,
+        schema: &serde_json::Value,
+        max_tokens: usize,
+    ) -> Result<(String, u64, u64), Box<dyn Error>> {
+        let constraint = JsonConstraint::new(schema);
+        let chat = format!("<|im_start|>user\n{prompt}<|im_end|>\n<|im_start|>assistant\n");
+        let prompt_ids = self
+            .tokenizer
+            .encode(chat, false)
+            .map_err(|e| e.to_string())?
+            .get_ids()
+            .to_vec();
+
+        let mut weights = self.weights.lock().unwrap();
+        let mut text = String::new();
+        let mut input = prompt_ids.clone();
+        let mut position = 0;
+        let mut generated = 0;
+
+        while generated < max_tokens {
+            let tokens = Tensor::new(input.as_slice(), &Device::Cpu)?.unsqueeze(0)?;
+            let logits = weights
+                .forward(&tokens, position)?
+                .squeeze(0)?
+                .to_vec1::<f32>()?;
+            position += input.len();
+
+            let mut ranked: Vec<u32> = (0..logits.len() as u32).collect();
+            ranked.sort_unstable_by(|a, b| logits[*b as usize].total_cmp(&logits[*a as usize]));
+            let token = constraint
+                .next_token(
+                    &text,
+                    ranked,
+                    |id| self.vocab.get(id as usize)?.as_deref(),
+                    self.eos,
+                )
+                .ok_or("No token of the vocabulary continues the JSON")?;
+
+            generated += 1;
+            if token == self.eos {
+                break;
+            }
+            text.push_str(self.vocab[token as usize].as_deref().unwrap_or_default());
+            input = vec![token];
+        }
+
+        if !constraint.is_complete(&text) {
+            return Err(format!(
+                "Local model reply incomplete after {} tokens (LOCAL_MAX_TOKENS)",
+                generated
+            )
+            .into());
+        }
+        Ok((text, prompt_ids.len() as u64, generated as u64))
+    }
+}
+
+/// Models loaded so far, by GGUF path
+static MODELS: Lazy<Mutex<HashMap<String, Arc<LocalModel>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Get a model, loading it on first use. Blocks while loading.
+pub fn model(model_path: &str, tokenizer_path: &str) -> Result<Arc<LocalModel>, Box<dyn Error>> {
+    let mut models = MODELS.lock().unwrap();
+    if let Some(model) = models.get(model_path) {
+        return Ok(Arc::clone(model));
+    }
+    let model = Arc::new(LocalModel::load(model_path, tokenizer_path)?);
+    models.insert(model_path.to_string(), Arc::clone(&model));
+    Ok(model)
+}
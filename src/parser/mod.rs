@@ -1,3 +1,17 @@
+pub mod cache;
+pub mod cassette;
+pub mod chunk;
+pub mod confidence;
+pub mod constrained;
+pub mod context;
+pub mod examples;
+pub mod grounding;
+#[cfg(feature = "local-llm")]
+pub mod local;
 pub mod ollama;
 pub mod pdf;
+pub mod preflight;
+pub mod prompts;
+pub mod router;
 pub mod section;
+pub mod validation;
@@ -1,124 +1,588 @@
-use reqwest::Client;
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::time::Instant;
 
 use crate::api::{
-    LlmBackend, OpenAIContent, OpenAIInput, OpenAIJsonSchema, OpenAIRequest, OpenAIResponse,
-    OpenAIResponseFormat,
+    LlmBackend, LlmProvider, ModelRoute, OpenAIContent, OpenAIInput, OpenAIJsonSchema,
+    OpenAIRequest, OpenAIResponse, OpenAIResponseFormat,
 };
 use crate::company::{
     AnnualReturnList, BusinessDetailsList, CertificateList, CompanyDetails, OfficeBearerList,
     RegistrationFee, ShareHolderList, StatedCapitalList,
 };
-use crate::config::llm::LLM_CONFIG;
+use crate::config::llm::LlmConfig;
+use crate::config::processing::ProcessingConfig;
 use crate::financial::{BalanceSheet, ProfitAndLoss};
-use crate::models::api::{JsonSchema, Message, OllamaChatRequest, OllamaChatResponse};
-
-/// Shared base prompt for all sections
-#[rustfmt::skip]
-const COMMON_PROMPT: &str = r#"
-You are a data extraction engine.
-
-IMPORTANT RULES:
-- If a value is missing, unknown, or unclear, return an EMPTY STRING "".
-- DO NOT use placeholder text ("Not provided", "Unknown", etc).
-- DO NOT include explanations as values.
-- Return ONLY valid JSON.
-"#;
-
-/// Build the final prompt using base + section rules
-fn build_prompt(parser: &SectionParser, section_name: &str, section_content: &str) -> String {
-    format!(
-        r#"{common}
-
-{rules}
-
-Extract information from the "{section_name}" section.
-
-Section:
-{content}
-"#,
-        common = COMMON_PROMPT,
-        rules = parser.prompt_rules(),
-        section_name = section_name,
-        content = section_content,
-    )
+use crate::models::api::{
+    JsonSchema, Message, OllamaChatRequest, OllamaChatResponse, OllamaOptions, TokenUsage,
+};
+use crate::models::schema::{field_guide, normalise_schema, SchemaProfile};
+use crate::parser::cache::ResponseCache;
+use crate::parser::cassette::CassetteMode;
+use crate::parser::chunk::{
+    is_office_bearer_row, is_shareholder_row, merge_rows, split_rows, Chunk, TableLayout,
+};
+use crate::parser::confidence::{field_confidence, ExtractionStrategy};
+use crate::parser::context::LlmContext;
+use crate::parser::examples::{render_examples, FewShotExample};
+use crate::parser::grounding::check_grounding;
+use crate::parser::validation::invalid_fields;
+
+/// Build the final prompt using the base template + field guide + section rules + few-shot examples
+///
+/// Ollama does not show the schema's field descriptions to the model, so they
+/// are listed in the prompt instead when the section goes to Ollama or a local
+/// model (its own route, or else the first provider). OpenAI reads them from
+/// the schema.
+///
+/// # Returns
+/// * The prompt and the template versions it was built from, each with a hash
+///   of its body (e.g. `base@1#9c8d7e6f+office_bearers@2#1a2b3c4d`), followed by `+fields@<hash>` when the field guide is injected and
+///   `+office_bearers.examples@<hash>` when examples are)
+fn build_prompt(
+    parser: &SectionParser,
+    section_name: &str,
+    section_content: &str,
+    route: Option<&ModelRoute>,
+    ctx: &LlmContext,
+) -> (String, String) {
+    let base = ctx.prompts.get("base");
+    let rules = ctx.prompts.get(parser.template_name());
+    let (examples, _) = render_examples(ctx.examples.for_section(parser.template_name()));
+    let backend = route.map_or(ctx.config.llm.primary_backend(), |r| r.backend);
+    let fields = parser.prompt_fields(backend).unwrap_or_default();
+
+    let prompt = base.render(&[
+        ("fields", &fields),
+        ("rules", &rules.body),
+        ("examples", &examples),
+        ("section_name", section_name),
+        ("content", section_content),
+    ]);
+
+    (prompt, parser.prompt_version(ctx, backend))
+}
+
+/// Value extracted from a section along with the tokens spent on it
+pub struct ParsedSection {
+    pub value: Value,
+    pub usage: TokenUsage,
+    pub prompt_version: String,
+    /// JSON pointers of string values not found in the section text
+    pub ungrounded: Vec<String>,
+    pub strategy: ExtractionStrategy,
+    /// JSON pointer → confidence of every extracted field
+    pub confidence: BTreeMap<String, f64>,
+    /// Model the section was re-extracted with after the first attempt failed
+    /// validation, e.g. `openai gpt-4.1`
+    pub escalated_to: Option<String>,
+    /// Model that answered, `backend=model`; for a chunked section answered
+    /// by several models, each of them, comma-separated
+    pub model: String,
+    /// Options sent with the request, as in the cache key (e.g. Ollama's
+    /// temperature and num_ctx); for a chunked section whose chunks were sent
+    /// with different options, e.g. an estimated num_ctx, a list of them
+    pub options: Value,
+}
+
+/// One request sent to a provider, recorded for the debug trace
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmExchange {
+    /// Provider label, e.g. `ollama qwen2.5:3b @ http://localhost:11434`
+    pub provider: String,
+    pub backend: &'static str,
+    pub model: String,
+    pub prompt: String,
+    /// Schema as sent to the backend
+    pub schema: Value,
+    pub latency_ms: u64,
+    /// Whether the reply came from the response cache
+    pub cached: bool,
+    /// Reply as received, before it was deserialized
+    pub raw_response: Option<Value>,
+    pub error: Option<String>,
 }
 
 /// Parse a section with structured output using Ollama's chat API
 pub async fn parse_section_with_structured_output<T>(
-    client: &Client,
+    ctx: &LlmContext,
+    prompt: String,
+) -> Result<(T, TokenUsage), Box<dyn std::error::Error>>
+where
+    T: DeserializeOwned + JsonSchema,
+{
+    let (value, reply) = parse_with_route(ctx, prompt, None, &mut Vec::new()).await?;
+    Ok((value, reply.usage))
+}
+
+/// Parse a prompt into `T`, sent to `route`'s model if given, otherwise to
+/// the providers' own models
+///
+/// # Returns
+/// * The parsed value and the reply it was parsed from
+async fn parse_with_route<T>(
+    ctx: &LlmContext,
     prompt: String,
-) -> Result<T, Box<dyn std::error::Error>>
+    route: Option<&ModelRoute>,
+    trace: &mut Vec<LlmExchange>,
+) -> Result<(T, StructuredReply), Box<dyn std::error::Error>>
 where
     T: DeserializeOwned + JsonSchema,
 {
     let schema = T::schema();
+    let schema_name = std::any::type_name::<T>()
+        .rsplit("::")
+        .next()
+        .unwrap()
+        .to_string();
+
+    let reply = request_structured_output(ctx, prompt, schema, schema_name, route, trace).await?;
+
+    // Only replies that deserialize are cached, so that a malformed one is
+    // asked for again rather than replayed on every run
+    let cache = ResponseCache::new(&ctx.config.llm.cache_dir, ctx.config.llm.cache_mode);
+    let value = match serde_json::from_value(reply.content.clone()) {
+        Ok(value) => value,
+        Err(e) => {
+            if reply.cached {
+                cache.evict(&reply.cache_key);
+            }
+            return Err(e.into());
+        }
+    };
+    if !reply.cached {
+        if let Err(e) = cache.put(&reply.cache_key, &reply.content) {
+            tracing::warn!(
+                "  Could not write LLM cache entry {}: {}",
+                &reply.cache_key[..12],
+                e
+            );
+        }
+    }
+    Ok((value, reply))
+}
+
+/// Parse a prompt into `T` and re-encode it as a JSON value
+async fn parse_as<T>(
+    ctx: &LlmContext,
+    prompt: String,
+    prompt_version: String,
+    route: Option<&ModelRoute>,
+    trace: &mut Vec<LlmExchange>,
+) -> Result<ParsedSection, Box<dyn Error>>
+where
+    T: DeserializeOwned + JsonSchema + Serialize,
+{
+    let (result, reply): (T, _) = parse_with_route(ctx, prompt, route, trace).await?;
+    Ok(ParsedSection {
+        value: serde_json::to_value(result)?,
+        usage: reply.usage,
+        prompt_version,
+        ungrounded: Vec::new(),
+        strategy: ExtractionStrategy::Single,
+        confidence: BTreeMap::new(),
+        escalated_to: None,
+        model: reply.model,
+        options: reply.options,
+    })
+}
+
+/// Reply to a structured output request
+struct StructuredReply {
+    content: Value,
+    usage: TokenUsage,
+    /// Key of the request in the response cache
+    cache_key: String,
+    /// Whether the reply came from the cache
+    cached: bool,
+    /// Model that answered, `backend=model`
+    model: String,
+    /// Options sent with the request
+    options: Value,
+}
+
+/// Estimate the context window a prompt needs: about 3 characters per token
+/// for the prompt and schema, plus room for a reply of `num_predict` tokens
+/// (or as long as the prompt if unset), rounded up to a multiple of 2048 and
+/// capped at `max_ctx`.
+fn estimate_num_ctx(prompt: &str, schema: &Value, num_predict: Option<i32>, max_ctx: u32) -> u32 {
+    let input = (prompt.len() + schema.to_string().len()).div_ceil(3) as u32;
+    let reply = match num_predict {
+        Some(n) if n > 0 => n as u32,
+        _ => input.max(1024),
+    };
+
+    let needed = (input + reply).div_ceil(2048) * 2048;
+    if needed > max_ctx {
+        tracing::warn!(
+            "  Prompt needs ~{} tokens of context but OLLAMA_MAX_CTX is {}; the reply may be truncated",
+            needed,
+            max_ctx
+        );
+    }
+    needed.min(max_ctx)
+}
+
+/// POST a request body to a backend and return the raw JSON reply.
+///
+/// In cassette replay mode the reply comes from the cassette and nothing is
+/// sent; in record mode every exchange is appended to it.
+///
+/// # Arguments
+/// * `url` - Endpoint to post to
+/// * `api_key` - Bearer token, if the backend needs one
+/// * `key` - Request key (same as the response cache key) used to index the cassette
+/// * `body` - Request body
+async fn post_json(
+    ctx: &LlmContext,
+    url: &str,
+    api_key: Option<&str>,
+    key: &str,
+    body: &Value,
+) -> Result<Value, Box<dyn Error>> {
+    let cassette = ctx.cassette();
+
+    if let Some(cassette) = cassette.filter(|c| c.mode() == CassetteMode::Replay) {
+        return cassette.replay(key).ok_or_else(|| {
+            format!(
+                "No recorded response for request {} in cassette {}",
+                &key[..12],
+                ctx.config.llm.cassette_path
+            )
+            .into()
+        });
+    }
+
+    let mut request = ctx.client.post(url).json(body);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let response: Value = request.send().await?.error_for_status()?.json().await?;
+
+    if let Some(cassette) = cassette.filter(|c| c.mode() == CassetteMode::Record) {
+        if let Err(e) = cassette.record(key, body, &response) {
+            tracing::warn!(
+                "  Could not record request {} to cassette: {}",
+                &key[..12],
+                e
+            );
+        }
+    }
+
+    Ok(response)
+}
+
+/// Ollama generation options of a request
+///
+/// # Arguments
+/// * `num_ctx` - Context window; the configured `OLLAMA_NUM_CTX` if `None`
+fn ollama_options(config: &LlmConfig, num_ctx: Option<u32>) -> OllamaOptions {
+    OllamaOptions {
+        temperature: config.ollama_temperature,
+        seed: config.ollama_seed,
+        num_ctx: num_ctx.or(config.ollama_num_ctx),
+        num_predict: config.ollama_num_predict,
+    }
+}
+
+/// Options sent with a request to `backend`, which are part of its cache key
+///
+/// # Arguments
+/// * `num_ctx` - Ollama context window estimated for the prompt; if `None`,
+///   only a configured `OLLAMA_NUM_CTX` is included
+pub(crate) fn request_options(
+    config: &LlmConfig,
+    backend: LlmBackend,
+    num_ctx: Option<u32>,
+) -> Value {
+    match backend {
+        LlmBackend::Ollama => {
+            serde_json::to_value(ollama_options(config, num_ctx)).expect("Ollama options serialize")
+        }
+        LlmBackend::OpenAI => Value::Null,
+        LlmBackend::Local => serde_json::json!({ "maxTokens": config.local_max_tokens }),
+    }
+}
+
+/// Providers to try for a request, in order, with their index in the router.
+///
+/// With a route, only providers of the route's backend are used, asked for
+/// the route's model. If none is configured, the backend's default provider
+/// is used outside the router.
+pub(crate) fn candidates(
+    ctx: &LlmContext,
+    route: Option<&ModelRoute>,
+) -> Vec<(Option<usize>, LlmProvider)> {
+    let plan = ctx
+        .router
+        .plan()
+        .into_iter()
+        .map(|i| (i, &ctx.router.providers()[i]));
+    let Some(route) = route else {
+        return plan.map(|(i, p)| (Some(i), p.clone())).collect();
+    };
 
-    match LLM_CONFIG.backend {
+    let matching: Vec<_> = plan
+        .filter(|(_, p)| p.backend == route.backend)
+        .map(|(i, p)| {
+            let mut provider = p.clone();
+            provider.model = route.model.clone();
+            (Some(i), provider)
+        })
+        .collect();
+    if !matching.is_empty() {
+        return matching;
+    }
+
+    let mut provider = ctx.config.llm.default_provider(route.backend);
+    provider.model = route.model.clone();
+    vec![(None, provider)]
+}
+
+/// Send a structured output request to the configured providers in the
+/// order chosen by the router, failing over to the next one on error.
+/// Every attempt is appended to `trace`.
+///
+/// The schema is rewritten for each provider's backend (references inlined,
+/// every object closed), see [`normalise_schema`].
+async fn request_structured_output(
+    ctx: &LlmContext,
+    prompt: String,
+    schema: Value,
+    schema_name: String,
+    route: Option<&ModelRoute>,
+    trace: &mut Vec<LlmExchange>,
+) -> Result<StructuredReply, Box<dyn std::error::Error>> {
+    let mut last_error: Option<Box<dyn Error>> = None;
+
+    for (index, provider) in candidates(ctx, route) {
+        let _in_flight = index.map(|i| ctx.router.start(i));
+        let schema = normalise_schema(&schema, SchemaProfile::for_backend(&provider.backend));
+        let mut exchange = LlmExchange {
+            provider: provider.label(),
+            backend: provider.backend.as_str(),
+            model: provider.model.clone(),
+            prompt: prompt.clone(),
+            schema,
+            latency_ms: 0,
+            cached: false,
+            raw_response: None,
+            error: None,
+        };
+
+        let started = Instant::now();
+        let result =
+            request_from_provider(ctx, &provider, &prompt, &schema_name, &mut exchange).await;
+        exchange.latency_ms = started.elapsed().as_millis() as u64;
+        exchange.error = result.as_ref().err().map(|e| e.to_string());
+        trace.push(exchange);
+
+        match result {
+            Ok(result) => {
+                if let Some(index) = index {
+                    ctx.router.record_success(index);
+                }
+                return Ok(result);
+            }
+            Err(e) => {
+                // Only transport errors say something about the host's health;
+                // a malformed reply is the model's fault
+                if let (Some(index), true) = (index, e.is::<reqwest::Error>()) {
+                    ctx.router.record_failure(index);
+                }
+                tracing::warn!("  LLM provider {} failed: {}", provider.label(), e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| "No LLM provider configured".into()))
+}
+
+/// Build an OpenAI Responses API request with a strict JSON schema
+fn openai_request(model: &str, prompt: &str, schema: Value, schema_name: &str) -> OpenAIRequest {
+    OpenAIRequest {
+        model: model.to_string(),
+        input: vec![OpenAIInput {
+            role: "user".to_string(),
+            content: vec![OpenAIContent {
+                r#type: "input_text".to_string(),
+                text: prompt.to_string(),
+            }],
+        }],
+        response_format: OpenAIResponseFormat {
+            r#type: "json_schema".to_string(),
+            json_schema: OpenAIJsonSchema {
+                name: schema_name.to_string(),
+                schema,
+                strict: true,
+            },
+        },
+    }
+}
+
+/// Send a structured output request to one provider, consulting the response
+/// cache first. Cache hits cost no tokens.
+///
+/// The reply is not written to the cache here: the caller does so once it
+/// has deserialized it.
+///
+/// The schema is taken from `exchange`, which is given the raw reply as soon
+/// as it arrives, so that a reply that fails to parse is still recorded.
+async fn request_from_provider(
+    ctx: &LlmContext,
+    provider: &LlmProvider,
+    prompt: &str,
+    schema_name: &str,
+    exchange: &mut LlmExchange,
+) -> Result<StructuredReply, Box<dyn std::error::Error>> {
+    let schema = exchange.schema.clone();
+    let cache = ResponseCache::new(&ctx.config.llm.cache_dir, ctx.config.llm.cache_mode);
+    let backend = provider.backend.as_str();
+    let model = provider.model.as_str();
+
+    let num_ctx = ctx.config.llm.ollama_num_ctx.unwrap_or_else(|| {
+        estimate_num_ctx(
+            prompt,
+            &schema,
+            ctx.config.llm.ollama_num_predict,
+            ctx.config.llm.ollama_max_ctx,
+        )
+    });
+    let options = request_options(&ctx.config.llm, provider.backend, Some(num_ctx));
+    let key = ResponseCache::key(backend, model, &options, prompt, &schema);
+    let route = ModelRoute {
+        backend: provider.backend,
+        model: model.to_string(),
+    };
+
+    if let Some(content) = cache.get(&key) {
+        tracing::info!("  LLM cache hit {} ({} {})", &key[..12], backend, model);
+        exchange.cached = true;
+        exchange.raw_response = Some(content.clone());
+        return Ok(StructuredReply {
+            content,
+            usage: TokenUsage::default(),
+            cache_key: key,
+            cached: true,
+            model: route.spec(),
+            options,
+        });
+    }
+    tracing::info!("  LLM cache miss {} ({})", &key[..12], provider.label());
+
+    let (content, prompt_tokens, completion_tokens) = match provider.backend {
         LlmBackend::Ollama => {
             let request = OllamaChatRequest {
-                model: &LLM_CONFIG.ollama_model,
+                model,
                 messages: vec![Message {
                     role: "user",
-                    content: &prompt,
+                    content: prompt,
                 }],
                 stream: false,
                 format: schema,
+                options: ollama_options(&ctx.config.llm, Some(num_ctx)),
+                keep_alive: ctx.config.llm.ollama_keep_alive.as_deref(),
             };
 
-            let res = client
-                .post(format!("{}/api/chat", LLM_CONFIG.ollama_url))
-                .json(&request)
-                .send()
-                .await?
-                .error_for_status()?;
+            let url = format!("{}/api/chat", provider.url);
+            let raw = post_json(ctx, &url, None, &key, &serde_json::to_value(&request)?).await?;
+            exchange.raw_response = Some(raw.clone());
 
-            let chat_response: OllamaChatResponse = res.json().await?;
-            Ok(serde_json::from_str(&chat_response.message.content)?)
+            let chat_response: OllamaChatResponse = serde_json::from_value(raw)?;
+            (
+                serde_json::from_str(&chat_response.message.content)?,
+                chat_response.prompt_eval_count,
+                chat_response.eval_count,
+            )
         }
 
-        LlmBackend::OpenAI { .. } => {
-            let api_key = LLM_CONFIG
-                .openai_api_key
-                .as_ref()
-                .ok_or("OPENAI_API_KEY missing")?;
-
-            let request = OpenAIRequest {
-                model: LLM_CONFIG.openai_model.clone(),
-                input: vec![OpenAIInput {
-                    role: "user".to_string(),
-                    content: vec![OpenAIContent {
-                        r#type: "input_text".to_string(),
-                        text: prompt,
-                    }],
-                }],
-                response_format: OpenAIResponseFormat {
-                    r#type: "json_schema".to_string(),
-                    json_schema: OpenAIJsonSchema {
-                        name: std::any::type_name::<T>()
-                            .rsplit("::")
-                            .next()
-                            .unwrap()
-                            .to_string(),
-                        schema,
-                    },
-                },
+        LlmBackend::OpenAI => {
+            // Replaying a cassette needs no credentials
+            let api_key = match provider.api_key.as_deref() {
+                Some(api_key) => Some(api_key),
+                None if ctx.config.llm.cassette_mode == CassetteMode::Replay => None,
+                None => return Err("OPENAI_API_KEY missing".into()),
             };
 
-            let res = client
-                .post("https://api.openai.com/v1/responses")
-                .bearer_auth(api_key)
-                .json(&request)
-                .send()
-                .await?
-                .error_for_status()?;
+            let request = openai_request(model, prompt, schema, schema_name);
+
+            let url = format!("{}/responses", provider.url.trim_end_matches('/'));
+            let raw = post_json(ctx, &url, api_key, &key, &serde_json::to_value(&request)?).await?;
+            exchange.raw_response = Some(raw.clone());
 
-            let response: OpenAIResponse = res.json().await?;
-            Ok(serde_json::from_value(response.output_parsed)?)
+            let response: OpenAIResponse = serde_json::from_value(raw)?;
+            let (input_tokens, output_tokens) = response
+                .usage
+                .map_or((0, 0), |u| (u.input_tokens, u.output_tokens));
+            (response.output_parsed, input_tokens, output_tokens)
         }
-    }
+
+        LlmBackend::Local => {
+            let (content, prompt_tokens, completion_tokens) =
+                generate_locally(&ctx.config.llm, model, prompt, &schema).await?;
+            exchange.raw_response = Some(content.clone());
+            (content, prompt_tokens, completion_tokens)
+        }
+    };
+
+    let usage = TokenUsage {
+        prompt_tokens,
+        completion_tokens,
+        estimated_cost: ctx
+            .config
+            .prices
+            .cost(model, prompt_tokens, completion_tokens),
+    };
+
+    Ok(StructuredReply {
+        content,
+        usage,
+        cache_key: key,
+        cached: false,
+        model: route.spec(),
+        options,
+    })
+}
+
+/// Run a request on an in-process model, on a blocking thread
+#[cfg(feature = "local-llm")]
+async fn generate_locally(
+    config: &LlmConfig,
+    model_path: &str,
+    prompt: &str,
+    schema: &Value,
+) -> Result<(Value, u64, u64), Box<dyn Error>> {
+    let tokenizer_path = config.local_tokenizer_for(model_path);
+    let max_tokens = config.local_max_tokens;
+    let (model_path, prompt, schema) = (model_path.to_string(), prompt.to_string(), schema.clone());
+
+    let (text, prompt_tokens, completion_tokens) = tokio::task::spawn_blocking(move || {
+        crate::parser::local::model(&model_path, &tokenizer_path)
+            .and_then(|model| model.generate(&prompt, &schema, max_tokens))
+            .map_err(|e| e.to_string())
+    })
+    .await??;
+    Ok((
+        serde_json::from_str(&text)?,
+        prompt_tokens,
+        completion_tokens,
+    ))
+}
+
+#[cfg(not(feature = "local-llm"))]
+async fn generate_locally(
+    _config: &LlmConfig,
+    _model_path: &str,
+    _prompt: &str,
+    _schema: &Value,
+) -> Result<(Value, u64, u64), Box<dyn Error>> {
+    Err("The local backend needs a build with `--features local-llm`".into())
 }
 
 /// Section parser enum to dispatch parsing based on section type
@@ -159,163 +623,520 @@ impl SectionParser {
         crate::ALL_SECTIONS.get(index).unwrap_or(&"Unknown Section")
     }
 
-    /// Section-specific prompt rules
-    pub fn prompt_rules(&self) -> &'static str {
+    /// Template names of every section that has a parser, in section order
+    pub fn template_names() -> Vec<&'static str> {
+        (0..crate::ALL_SECTIONS.len())
+            .filter_map(Self::from_section_index)
+            .map(|parser| parser.template_name())
+            .collect()
+    }
+
+    /// Name of the prompt template holding this section's rules
+    pub fn template_name(&self) -> &'static str {
         match self {
-            #[rustfmt::skip]
-SectionParser::CompanyDetails => {
-    r#"
-The following section represents KEY-VALUE company metadata, NOT a table.
-
-The layout looks like:
-- File No.
-- Date Incorporated
-- Name
-- Nature
-- Type
-- Status
-- Category
-- Sub Category
-- Registered Office Address
-- Effective date for Registered Office Address
-
-EXTRACTION RULES (MUST FOLLOW STRICTLY):
-
-File No. rules:
-- Starts with a letter (e.g C, P) followed by a number. E.g., C12, C4235, P15.
-- The File No. is found close to the Name. Do not confuse these two.
-
-Category rules:
-- Allowed common values: DOMESTIC, FOREIGN(DOM BRANCH), AUTHORISED COMPANY
-- If Category is EMPTY, return "".
-- If Category contains another meaningful value, KEEP it EXACTLY.
-- DO NOT normalize or guess Category values.
-
-Type rules:
-- Common value: LIMITED BY SHARES
-- If Type is EMPTY, return "".
-- If Type contains another meaningful value, KEEP it EXACTLY.
-- DO NOT normalize or guess Type values.
-"#
-},
-            #[rustfmt::skip]
-            SectionParser::BusinessDetails => {
-                r#"
-The following section represents a TABLE with these columns:
-1. Business Name
-2. Nature of Business
-3. Principal Place of Business
-
-Table rules:
-- Each logical row starts with either a Business Name or a single "." character.
-- If a row starts with ".", the Business Name is empty. Store it EXACTLY as ".".
-- Rows may span multiple lines; merge wrapped lines into one row.
-- Ignore headers, repeated titles, page numbers, footers.
-- Do not invent or infer data.
-"#
-            },
-            #[rustfmt::skip]
-            SectionParser::OfficeBearers => {
-                r#"
-The following section represents a TABLE with these columns:
-1. Position
-2. Name
-3. Service Address
-4. Appointed Date
-
-CRITICAL EXTRACTION RULES (MUST FOLLOW):
-- Position MUST contain only the role (e.g. DIRECTOR, SECRETARY, CHAIRMAN).
-- Name MUST contain ONLY the entity name (person OR company).
-- REMOVE the position title if it appears inside the Name.
-  Example:
-  Input: "DIRECTOR BEDEUX JEAN ALAIN"
-  Output:
-    Position = "DIRECTOR"
-    Name = "BEDEUX JEAN ALAIN"
-
-- Service Address may include street, city, and country.
-- Country MUST be the LAST word in the address field.
-- entityType MUST ALWAYS be an EMPTY STRING "".
-- If a value is missing, return "" (empty string).
-- DO NOT invent, infer, or normalize names.
-
-Return ONLY valid JSON.
-"#
-            },
+            SectionParser::CompanyDetails => "company_details",
+            SectionParser::BusinessDetails => "business_details",
+            SectionParser::StatedCapital => "stated_capital",
+            SectionParser::Certificates => "certificates",
+            SectionParser::OfficeBearers => "office_bearers",
+            SectionParser::ShareHolders => "shareholders",
+            SectionParser::AnnualReturns => "annual_returns",
+            SectionParser::RegistrationFee => "registration_fee",
+            SectionParser::BalanceSheet => "balance_sheet",
+            SectionParser::ProfitAndLoss => "profit_and_loss",
+        }
+    }
+
+    /// Key wrapping the list in list-shaped section models
+    /// (e.g. `officeBearers` in `OfficeBearerList`)
+    pub fn list_key(&self) -> Option<&'static str> {
+        match self {
+            SectionParser::BusinessDetails => Some("businessDetails"),
+            SectionParser::StatedCapital => Some("statedCapitals"),
+            SectionParser::Certificates => Some("certificates"),
+            SectionParser::OfficeBearers => Some("officeBearers"),
+            SectionParser::ShareHolders => Some("shareHolders"),
+            SectionParser::AnnualReturns => Some("annualReturns"),
+            _ => None,
+        }
+    }
+
+    /// Check a JSON value against this section's model and return it in the
+    /// model's canonical shape. A bare list (as written in the output files)
+    /// is wrapped in the model's list key first.
+    pub fn coerce(&self, value: Value) -> Result<Value, Box<dyn Error>> {
+        let value = match (self.list_key(), value) {
+            (Some(key), Value::Array(items)) => serde_json::json!({ key: items }),
+            (_, other) => other,
+        };
+
+        fn roundtrip<T: DeserializeOwned + Serialize>(
+            value: Value,
+        ) -> Result<Value, Box<dyn Error>> {
+            Ok(serde_json::to_value(serde_json::from_value::<T>(value)?)?)
+        }
 
-            _ => "",
+        match self {
+            SectionParser::CompanyDetails => roundtrip::<CompanyDetails>(value),
+            SectionParser::BusinessDetails => roundtrip::<BusinessDetailsList>(value),
+            SectionParser::StatedCapital => roundtrip::<StatedCapitalList>(value),
+            SectionParser::Certificates => roundtrip::<CertificateList>(value),
+            SectionParser::OfficeBearers => roundtrip::<OfficeBearerList>(value),
+            SectionParser::ShareHolders => roundtrip::<ShareHolderList>(value),
+            SectionParser::AnnualReturns => roundtrip::<AnnualReturnList>(value),
+            SectionParser::RegistrationFee => roundtrip::<RegistrationFee>(value),
+            SectionParser::BalanceSheet => roundtrip::<BalanceSheet>(value),
+            SectionParser::ProfitAndLoss => roundtrip::<ProfitAndLoss>(value),
+        }
+    }
+
+    /// JSON schema of this section's model, as generated from its type
+    pub fn schema(&self) -> Value {
+        match self {
+            SectionParser::CompanyDetails => CompanyDetails::schema(),
+            SectionParser::BusinessDetails => BusinessDetailsList::schema(),
+            SectionParser::StatedCapital => StatedCapitalList::schema(),
+            SectionParser::Certificates => CertificateList::schema(),
+            SectionParser::OfficeBearers => OfficeBearerList::schema(),
+            SectionParser::ShareHolders => ShareHolderList::schema(),
+            SectionParser::AnnualReturns => AnnualReturnList::schema(),
+            SectionParser::RegistrationFee => RegistrationFee::schema(),
+            SectionParser::BalanceSheet => BalanceSheet::schema(),
+            SectionParser::ProfitAndLoss => ProfitAndLoss::schema(),
+        }
+    }
+
+    /// Add a corrected extraction to this section's few-shot example bank
+    ///
+    /// # Arguments
+    /// * `ctx` - Run whose example bank (`FEW_SHOT_DIR`) receives the example
+    /// * `section_content` - Section text the output was extracted from
+    /// * `corrected_output` - Corrected value, either in model shape or as
+    ///   written in the output JSON files
+    pub fn add_example(
+        &self,
+        ctx: &LlmContext,
+        section_content: &str,
+        corrected_output: Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let example = FewShotExample {
+            input: section_content.to_string(),
+            output: self.coerce(corrected_output)?,
+        };
+        ctx.examples.append(self.template_name(), &example)?;
+        Ok(())
+    }
+
+    /// Field descriptions written into the prompt for a backend that does not
+    /// read them from the schema, or `None` if the backend does
+    fn prompt_fields(&self, backend: LlmBackend) -> Option<String> {
+        match backend {
+            LlmBackend::Ollama | LlmBackend::Local => Some(format!(
+                "Fields:\n{}",
+                field_guide(&normalise_schema(&self.schema(), SchemaProfile::Ollama))
+            )),
+            LlmBackend::OpenAI => None,
+        }
+    }
+
+    /// Templates, field guide and examples behind this section's prompt when
+    /// sent to `backend`, e.g.
+    /// `base@1#9c8d7e6f+office_bearers@2#1a2b3c4d+fields@5e6f7a8b+office_bearers.examples@1a2b3c4d`
+    pub fn prompt_version(&self, ctx: &LlmContext, backend: LlmBackend) -> String {
+        let mut version = format!(
+            "{}+{}",
+            ctx.prompts.get("base").fingerprint(),
+            ctx.prompts.get(self.template_name()).fingerprint()
+        );
+        if let Some(fields) = self.prompt_fields(backend) {
+            let hash = format!("{:x}", Sha256::digest(fields.as_bytes()));
+            version.push_str(&format!("+fields@{}", &hash[..8]));
         }
+        if let (_, Some(hash)) = render_examples(ctx.examples.for_section(self.template_name())) {
+            version.push_str(&format!("+{}.examples@{}", self.template_name(), hash));
+        }
+        version
+    }
+
+    /// Section-specific prompt rules
+    pub fn prompt_rules<'a>(&self, ctx: &'a LlmContext) -> &'a str {
+        &ctx.prompts.get(self.template_name()).body
+    }
+
+    /// Model this section is sent to, from `LLM_MODEL_<SECTION>`.
+    /// `None` uses the providers' own models.
+    pub fn model_route(&self, config: &LlmConfig) -> Option<ModelRoute> {
+        config.section_models.get(self.template_name()).cloned()
+    }
+
+    /// Model to retry this section with when the first reply fails validation,
+    /// from `LLM_ESCALATE_<SECTION>` or else `LLM_ESCALATION_MODEL`
+    pub fn escalation_route(&self, config: &LlmConfig) -> Option<ModelRoute> {
+        config
+            .section_escalations
+            .get(self.template_name())
+            .or(config.escalation_model.as_ref())
+            .cloned()
+    }
+
+    /// Model this section is sent to through the batch API: its own model if
+    /// it is an OpenAI one, otherwise `OPENAI_MODEL`
+    pub fn batch_route(&self, config: &LlmConfig) -> ModelRoute {
+        self.model_route(config)
+            .filter(|route| route.backend == LlmBackend::OpenAI)
+            .unwrap_or_else(|| ModelRoute {
+                backend: LlmBackend::OpenAI,
+                model: config.openai_model.clone(),
+            })
+    }
+
+    /// Build the OpenAI Responses API request body for a section, to be sent
+    /// through a batch endpoint instead of synchronously
+    ///
+    /// The model is the section's [`batch_route`](Self::batch_route). Long
+    /// tables are sent whole, not in chunks. The prompt version and model go
+    /// in the request's `metadata`, which the reply echoes.
+    pub fn batch_request(
+        &self,
+        ctx: &LlmContext,
+        section_content: &str,
+        section_name: &str,
+    ) -> Result<Value, Box<dyn Error>> {
+        let route = self.batch_route(&ctx.config.llm);
+        let (prompt, version) =
+            build_prompt(self, section_name, section_content, Some(&route), ctx);
+        let schema = normalise_schema(&self.schema(), SchemaProfile::OpenAI);
+        let request = openai_request(&route.model, &prompt, schema, self.template_name());
+
+        // Echoed back in the response, so that ingest can stamp the version
+        let mut body = serde_json::to_value(request)?;
+        body["metadata"] = serde_json::json!({ "promptVersion": version, "model": route.spec() });
+        Ok(body)
     }
 
     /// Parse section content using correct structured output type
+    ///
+    /// The section is sent to its own model if one is configured. If the
+    /// reply fails to parse or has fields that fail validation, it is
+    /// extracted again with the escalation model, if any.
+    ///
+    /// Long table sections are split into overlapping row-aligned chunks that
+    /// are extracted one by one and merged back into a single list. Every
+    /// field is given a confidence score, then checked against the section
+    /// text according to `GROUNDING_POLICY`.
     pub async fn parse(
         &self,
-        client: &Client,
+        ctx: &LlmContext,
         section_content: &str,
         section_name: &str,
-    ) -> Result<Value, Box<dyn Error>> {
-        let prompt = build_prompt(self, section_name, section_content);
+    ) -> Result<ParsedSection, Box<dyn Error>> {
+        self.parse_traced(ctx, section_content, section_name, &mut Vec::new())
+            .await
+    }
+
+    /// Like [`SectionParser::parse`], also recording every request sent to a
+    /// provider in `trace`, whether or not the section could be parsed
+    pub async fn parse_traced(
+        &self,
+        ctx: &LlmContext,
+        section_content: &str,
+        section_name: &str,
+        trace: &mut Vec<LlmExchange>,
+    ) -> Result<ParsedSection, Box<dyn Error>> {
+        let route = self.model_route(&ctx.config.llm);
+        let first = self
+            .extract(ctx, section_content, section_name, route.as_ref(), trace)
+            .await;
+
+        let reason = match &first {
+            Ok(parsed) => {
+                let invalid = invalid_fields(&parsed.value);
+                (!invalid.is_empty()).then(|| format!("invalid fields {}", invalid.join(", ")))
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        let escalation = self
+            .escalation_route(&ctx.config.llm)
+            .filter(|e| Some(e) != route.as_ref());
+
+        let mut parsed = match (reason, escalation) {
+            (Some(reason), Some(escalation)) => {
+                tracing::warn!(
+                    "  {} escalated to {} ({})",
+                    section_name,
+                    escalation.label(),
+                    reason
+                );
+                let spent = first.map(|p| p.usage).unwrap_or_default();
+                let mut parsed = self
+                    .extract(ctx, section_content, section_name, Some(&escalation), trace)
+                    .await?;
+                parsed.usage += spent;
+                parsed.escalated_to = Some(escalation.label());
+                parsed
+            }
+            _ => first?,
+        };
+
+        self.score(
+            &mut parsed,
+            section_content,
+            section_name,
+            &ctx.config.processing,
+        );
+        Ok(parsed)
+    }
+
+    /// Give every field of an extracted section a confidence score, then
+    /// check it against the section text according to `GROUNDING_POLICY`
+    pub fn score(
+        &self,
+        parsed: &mut ParsedSection,
+        section_content: &str,
+        section_name: &str,
+        processing: &ProcessingConfig,
+    ) {
+        // Scored before grounding so that cleared values keep their low score
+        parsed.confidence = field_confidence(
+            &parsed.value,
+            section_content,
+            &parsed.strategy,
+            self.list_key(),
+        );
+
+        parsed.ungrounded = check_grounding(
+            &mut parsed.value,
+            section_content,
+            processing.grounding_policy,
+            processing.grounding_min_score,
+        );
+        if !parsed.ungrounded.is_empty() {
+            tracing::warn!(
+                "  {} has values not found in the source: {}",
+                section_name,
+                parsed.ungrounded.join(", ")
+            );
+        }
+    }
+
+    /// Run the extraction, chunked if the section is a long table
+    async fn extract(
+        &self,
+        ctx: &LlmContext,
+        section_content: &str,
+        section_name: &str,
+        route: Option<&ModelRoute>,
+        trace: &mut Vec<LlmExchange>,
+    ) -> Result<ParsedSection, Box<dyn Error>> {
+        if let Some(layout) = self.table_layout() {
+            let chunks = split_rows(
+                section_content,
+                layout.is_row_start,
+                ctx.config.processing.chunk_max_chars,
+                ctx.config.processing.chunk_overlap_rows,
+            );
+            if chunks.len() > 1 {
+                return self
+                    .parse_chunks(ctx, &chunks, section_name, layout.key_fields, route, trace)
+                    .await;
+            }
+        }
+
+        let (prompt, version) = build_prompt(self, section_name, section_content, route, ctx);
+        self.parse_prompt(ctx, prompt, version, route, trace).await
+    }
 
+    /// Row layout of the table sections that may be chunked
+    fn table_layout(&self) -> Option<TableLayout> {
         match self {
-            SectionParser::CompanyDetails => {
-                let result: CompanyDetails =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+            SectionParser::OfficeBearers => Some(TableLayout {
+                is_row_start: is_office_bearer_row,
+                key_fields: &["position", "name", "appointedDate"],
+            }),
+            SectionParser::ShareHolders => Some(TableLayout {
+                is_row_start: is_shareholder_row,
+                key_fields: &["name", "shareType", "numShares"],
+            }),
+            _ => None,
+        }
+    }
+
+    /// Extract every chunk of a table section and merge the rows
+    async fn parse_chunks(
+        &self,
+        ctx: &LlmContext,
+        chunks: &[Chunk],
+        section_name: &str,
+        key_fields: &[&str],
+        route: Option<&ModelRoute>,
+        trace: &mut Vec<LlmExchange>,
+    ) -> Result<ParsedSection, Box<dyn Error>> {
+        let list_key = self.list_key().ok_or("chunked section has no list key")?;
+        tracing::info!("  {} split into {} chunks", section_name, chunks.len());
+
+        let mut usage = TokenUsage::default();
+        let mut prompt_version = String::new();
+        let mut models: Vec<String> = Vec::new();
+        let mut options: Vec<Value> = Vec::new();
+        let mut parts = Vec::with_capacity(chunks.len());
+
+        for chunk in chunks {
+            let (prompt, version) = build_prompt(self, section_name, &chunk.text, route, ctx);
+            let parsed = self
+                .parse_prompt(ctx, prompt, version, route, trace)
+                .await?;
+
+            usage += parsed.usage;
+            prompt_version = parsed.prompt_version;
+            if !models.contains(&parsed.model) {
+                models.push(parsed.model);
             }
+            options.push(parsed.options);
+            match parsed.value.get(list_key) {
+                Some(Value::Array(rows)) => parts.push(rows.clone()),
+                _ => parts.push(Vec::new()),
+            }
+        }
+
+        let extracted: usize = parts.iter().map(Vec::len).sum();
+        let overlaps: Vec<usize> = chunks.iter().map(|chunk| chunk.overlap).collect();
+        let (rows, row_sightings) = merge_rows(parts, &overlaps, key_fields);
+        tracing::info!(
+            "  {} merged {} rows ({} duplicates from overlap)",
+            section_name,
+            rows.len(),
+            extracted - rows.len()
+        );
 
+        // Chunks sent with the same options, e.g. a fixed num_ctx, record them once
+        options.dedup();
+        let options = match options.len() {
+            1 => options.remove(0),
+            _ => Value::Array(options),
+        };
+
+        Ok(ParsedSection {
+            value: serde_json::json!({ list_key: rows }),
+            usage,
+            prompt_version,
+            ungrounded: Vec::new(),
+            strategy: ExtractionStrategy::Chunked { row_sightings },
+            confidence: BTreeMap::new(),
+            escalated_to: None,
+            model: models.join(","),
+            options,
+        })
+    }
+
+    /// Send a built prompt and deserialize the reply into this section's model
+    async fn parse_prompt(
+        &self,
+        ctx: &LlmContext,
+        prompt: String,
+        version: String,
+        route: Option<&ModelRoute>,
+        trace: &mut Vec<LlmExchange>,
+    ) -> Result<ParsedSection, Box<dyn Error>> {
+        match self {
+            SectionParser::CompanyDetails => {
+                parse_as::<CompanyDetails>(ctx, prompt, version, route, trace).await
+            }
             SectionParser::BusinessDetails => {
-                let result: BusinessDetailsList =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<BusinessDetailsList>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::StatedCapital => {
-                let result: StatedCapitalList =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<StatedCapitalList>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::Certificates => {
-                let result: CertificateList =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<CertificateList>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::OfficeBearers => {
-                let result: OfficeBearerList =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<OfficeBearerList>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::ShareHolders => {
-                let result: ShareHolderList =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<ShareHolderList>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::AnnualReturns => {
-                let result: AnnualReturnList =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<AnnualReturnList>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::RegistrationFee => {
-                let result: RegistrationFee =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<RegistrationFee>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::BalanceSheet => {
-                let result: BalanceSheet =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<BalanceSheet>(ctx, prompt, version, route, trace).await
             }
-
             SectionParser::ProfitAndLoss => {
-                let result: ProfitAndLoss =
-                    parse_section_with_structured_output(client, prompt).await?;
-                Ok(serde_json::to_value(result)?)
+                parse_as::<ProfitAndLoss>(ctx, prompt, version, route, trace).await
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_coerce_wraps_bare_lists() {
+        let bearer = json!({
+            "position": "DIRECTOR",
+            "name": "BEDEUX JEAN ALAIN",
+            "address": "PORT LOUIS MAURITIUS",
+            "country": "MAURITIUS",
+            "appointedDate": "01/02/2003",
+            "entityType": ""
+        });
+
+        let coerced = SectionParser::OfficeBearers
+            .coerce(json!([bearer.clone()]))
+            .unwrap();
+        assert_eq!(coerced, json!({ "officeBearers": [bearer] }));
+    }
+
+    #[test]
+    fn test_estimate_num_ctx() {
+        let schema = json!({});
+        assert_eq!(estimate_num_ctx("short", &schema, None, 32768), 2048);
+
+        let long = "x".repeat(9000);
+        assert_eq!(estimate_num_ctx(&long, &schema, None, 32768), 6144);
+        assert_eq!(estimate_num_ctx(&long, &schema, Some(500), 32768), 4096);
+        assert_eq!(estimate_num_ctx(&long, &schema, None, 4096), 4096);
+    }
+
+    fn context(pairs: &[(&str, &str)]) -> LlmContext {
+        let settings: crate::config::Settings = pairs.iter().copied().collect();
+        LlmContext::new(crate::config::Config::from_settings(&settings).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_prompt_version_covers_field_guide() {
+        let ctx = context(&[]);
+        let ollama = SectionParser::OfficeBearers.prompt_version(&ctx, LlmBackend::Ollama);
+        let openai = SectionParser::OfficeBearers.prompt_version(&ctx, LlmBackend::OpenAI);
+        assert!(ollama.contains("+fields@"), "{}", ollama);
+        assert!(!openai.contains("+fields@"), "{}", openai);
+    }
+
+    #[test]
+    fn test_prompts_are_per_context() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("base.txt"), "version: 9\n---\n{{rules}}").unwrap();
+        let prompt_dir = dir.path().to_str().unwrap();
+
+        let builtin = context(&[("FEW_SHOT_DIR", prompt_dir)]);
+        let tuned = context(&[("PROMPT_DIR", prompt_dir), ("FEW_SHOT_DIR", prompt_dir)]);
+        let builtin = SectionParser::OfficeBearers.prompt_version(&builtin, LlmBackend::OpenAI);
+        let tuned = SectionParser::OfficeBearers.prompt_version(&tuned, LlmBackend::OpenAI);
+        assert!(builtin.starts_with("base@3#"), "{}", builtin);
+        assert!(tuned.starts_with("base@9#"), "{}", tuned);
+    }
+
+    #[test]
+    fn test_coerce_rejects_wrong_shape() {
+        assert!(SectionParser::CompanyDetails
+            .coerce(json!({"orgName": "ACME"}))
+            .is_err());
+    }
+}
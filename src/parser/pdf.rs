@@ -8,7 +8,7 @@
 ///
 /// # Panics
 /// * If the file cannot be read or PDF extraction fails
-pub fn get_text_from_pdf(pdf_path: &str) -> String {
+pub fn get_text_from_pdf(pdf_path: impl AsRef<std::path::Path>) -> String {
     let bytes = std::fs::read(pdf_path).unwrap();
     pdf_extract::extract_text_from_mem(&bytes).unwrap()
 }
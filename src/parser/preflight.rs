@@ -0,0 +1,373 @@
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::api::{LlmBackend, LlmProvider, ModelRoute};
+use crate::config::llm::LlmConfig;
+use crate::parser::cassette::CassetteMode;
+use crate::parser::context::LlmContext;
+use crate::parser::ollama::{candidates, SectionParser};
+
+/// How long to wait for the backend to answer a health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+/// Whether an installed Ollama model satisfies the configured name.
+/// A name without a tag means `:latest`.
+fn model_matches(installed: &str, wanted: &str) -> bool {
+    installed == wanted || (!wanted.contains(':') && installed == format!("{wanted}:latest"))
+}
+
+/// Check that Ollama is reachable and has `model`, pulling it if `auto_pull` is set
+///
+/// # Arguments
+/// * `url` - Ollama base URL
+/// * `model` - Model name, e.g. `qwen2.5:3b`
+/// * `auto_pull` - Pull the model instead of failing when it is missing
+pub async fn check_ollama(
+    client: &Client,
+    url: &str,
+    model: &str,
+    auto_pull: bool,
+) -> Result<(), Box<dyn Error>> {
+    let tags: OllamaTags = client
+        .get(format!("{}/api/tags", url))
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| {
+            format!(
+                "Cannot reach Ollama at {} ({}). Is `ollama serve` running?",
+                url, e
+            )
+        })?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    if tags.models.iter().any(|m| model_matches(&m.name, model)) {
+        tracing::info!("Preflight: Ollama at {} has model {}", url, model);
+        return Ok(());
+    }
+
+    if !auto_pull {
+        let available: Vec<&str> = tags.models.iter().map(|m| m.name.as_str()).collect();
+        return Err(format!(
+            "Model {} is not available in Ollama at {} (available: {}). \
+             Run `ollama pull {}` or set OLLAMA_AUTO_PULL=true",
+            model,
+            url,
+            if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            },
+            model
+        )
+        .into());
+    }
+
+    tracing::info!("Preflight: pulling model {} into Ollama at {}", model, url);
+    client
+        .post(format!("{}/api/pull", url))
+        .json(&serde_json::json!({ "model": model, "stream": false }))
+        .send()
+        .await?
+        .error_for_status()
+        .map_err(|e| format!("Could not pull model {}: {}", model, e))?;
+    tracing::info!("Preflight: pulled model {}", model);
+    Ok(())
+}
+
+/// Check that the OpenAI API accepts `api_key` and serves `model`
+///
+/// # Arguments
+/// * `url` - OpenAI API base URL, e.g. `https://api.openai.com/v1`
+/// * `model` - Model name, e.g. `gpt-4.1-mini`
+/// * `api_key` - API key, if configured
+pub async fn check_openai(
+    client: &Client,
+    url: &str,
+    model: &str,
+    api_key: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let api_key = api_key.ok_or("OPENAI_API_KEY missing")?;
+
+    let response = client
+        .get(format!("{}/models/{}", url.trim_end_matches('/'), model))
+        .bearer_auth(api_key)
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Cannot reach the OpenAI API at {} ({})", url, e))?;
+
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            Err("OpenAI rejected OPENAI_API_KEY".into())
+        }
+        StatusCode::NOT_FOUND => {
+            Err(format!("Model {} is not available with this OpenAI API key", model).into())
+        }
+        _ => {
+            response.error_for_status()?;
+            tracing::info!("Preflight: OpenAI serves model {}", model);
+            Ok(())
+        }
+    }
+}
+
+/// Check one provider
+async fn check_provider(ctx: &LlmContext, provider: &LlmProvider) -> Result<(), Box<dyn Error>> {
+    let config = &ctx.config.llm;
+    match provider.backend {
+        LlmBackend::Ollama => {
+            check_ollama(
+                &ctx.client,
+                &provider.url,
+                &provider.model,
+                config.ollama_auto_pull,
+            )
+            .await
+        }
+        LlmBackend::OpenAI => {
+            check_openai(
+                &ctx.client,
+                &provider.url,
+                &provider.model,
+                provider.api_key.as_deref(),
+            )
+            .await
+        }
+        LlmBackend::Local => check_local(
+            &provider.model,
+            &config.local_tokenizer_for(&provider.model),
+        ),
+    }
+}
+
+/// Check that this build can run local models and that the model files exist
+///
+/// # Arguments
+/// * `model` - Path of the GGUF file
+/// * `tokenizer` - Path of its `tokenizer.json`
+fn check_local(model: &str, tokenizer: &str) -> Result<(), Box<dyn Error>> {
+    if !cfg!(feature = "local-llm") {
+        return Err("The local backend needs a build with `--features local-llm`".into());
+    }
+    for path in [model, tokenizer] {
+        if !std::path::Path::new(path).is_file() {
+            return Err(format!("Local model file {} not found", path).into());
+        }
+    }
+    tracing::info!("Preflight: local model {} found", model);
+    Ok(())
+}
+
+/// Models sections are routed or escalated to, with the sections using each
+fn routed_models(config: &LlmConfig) -> BTreeMap<String, (ModelRoute, Vec<&'static str>)> {
+    let mut routes: BTreeMap<String, (ModelRoute, Vec<&'static str>)> = BTreeMap::new();
+    let parsers = (0..crate::ALL_SECTIONS.len()).filter_map(SectionParser::from_section_index);
+    for parser in parsers {
+        for route in [parser.model_route(config), parser.escalation_route(config)]
+            .into_iter()
+            .flatten()
+        {
+            let (_, sections) = routes
+                .entry(route.spec())
+                .or_insert_with(|| (route, Vec::new()));
+            if !sections.contains(&parser.template_name()) {
+                sections.push(parser.template_name());
+            }
+        }
+    }
+    routes
+}
+
+/// Check every configured provider before a batch starts, so that a stopped
+/// server or a missing model fails the run instead of every section.
+/// Providers that fail are taken out of rotation; the run only fails if none
+/// passes. Models routed per section or escalated to are checked on the
+/// providers they would be sent to, and the run fails if one of them is
+/// served by none. Skipped when replaying a cassette, which needs no backend.
+pub async fn preflight(ctx: &LlmContext) -> Result<(), Box<dyn Error>> {
+    if ctx.config.llm.cassette_mode == CassetteMode::Replay {
+        return Ok(());
+    }
+
+    let mut errors = Vec::new();
+    for (index, provider) in ctx.router.providers().iter().enumerate() {
+        if let Err(e) = check_provider(ctx, provider).await {
+            tracing::warn!("Preflight: {} unavailable: {}", provider.label(), e);
+            ctx.router.mark_down(index);
+            errors.push(e.to_string());
+        }
+    }
+
+    if errors.len() == ctx.router.providers().len() {
+        return Err(errors.join("; ").into());
+    }
+
+    let providers = ctx.router.providers();
+    for (spec, (route, sections)) in routed_models(&ctx.config.llm) {
+        let mut failures = Vec::new();
+        let mut served = false;
+        for (index, provider) in candidates(ctx, Some(&route)) {
+            // The provider already passed with this very model
+            if index
+                .is_some_and(|i| providers[i].model == route.model && ctx.router.is_available(i))
+            {
+                served = true;
+                break;
+            }
+            match check_provider(ctx, &provider).await {
+                Ok(()) => {
+                    served = true;
+                    break;
+                }
+                Err(e) => failures.push(e.to_string()),
+            }
+        }
+        if !served {
+            return Err(format!(
+                "Model {} of {}: {}",
+                spec,
+                sections.join(", "),
+                failures.join("; ")
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockLlmServer;
+
+    #[test]
+    fn test_model_matches() {
+        assert!(model_matches("qwen2.5:3b", "qwen2.5:3b"));
+        assert!(model_matches("llama3:latest", "llama3"));
+        assert!(!model_matches("qwen2.5:7b", "qwen2.5:3b"));
+        assert!(!model_matches("llama3:8b", "llama3"));
+    }
+
+    #[tokio::test]
+    async fn test_check_ollama() {
+        let server = MockLlmServer::start().unwrap();
+        let client = Client::new();
+        server.set_models(&["llama3:latest"]);
+
+        assert!(check_ollama(&client, &server.url(), "llama3", false)
+            .await
+            .is_ok());
+
+        let err = check_ollama(&client, &server.url(), "qwen2.5:3b", false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("available: llama3:latest"));
+
+        check_ollama(&client, &server.url(), "qwen2.5:3b", true)
+            .await
+            .unwrap();
+        assert!(check_ollama(&client, &server.url(), "qwen2.5:3b", false)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_ollama_unreachable() {
+        let url = {
+            let server = MockLlmServer::start().unwrap();
+            server.url()
+        };
+        let err = check_ollama(&Client::new(), &url, "qwen2.5:3b", false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Cannot reach Ollama"));
+    }
+
+    #[test]
+    fn test_check_local_missing_model() {
+        let err = check_local("no/such/model.gguf", "no/such/tokenizer.json")
+            .unwrap_err()
+            .to_string();
+        if cfg!(feature = "local-llm") {
+            assert!(err.contains("no/such/model.gguf not found"));
+        } else {
+            assert!(err.contains("--features local-llm"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preflight_checks_routed_models() {
+        let server = MockLlmServer::start().unwrap();
+        server.set_models(&["mock-model"]);
+        let openai_url = format!("{}/v1", server.url());
+        let settings: crate::config::Settings = [
+            ("LLM_BACKEND", "ollama"),
+            ("OLLAMA_URL", server.url().as_str()),
+            ("OLLAMA_MODEL", "mock-model"),
+            ("LLM_CACHE", "off"),
+            ("LLM_MODEL_OFFICE_BEARERS", "ollama=big-model"),
+            ("LLM_ESCALATION_MODEL", "openai=gpt-4.1"),
+            ("OPENAI_URL", openai_url.as_str()),
+            ("OPENAI_API_KEY", "sk-test"),
+        ]
+        .into_iter()
+        .collect();
+        let config = crate::config::Config::from_settings(&settings).unwrap();
+        let ctx = LlmContext::new(config).unwrap();
+
+        // The providers' own model is there, the routed one is not
+        let err = preflight(&ctx).await.unwrap_err().to_string();
+        assert!(
+            err.contains("ollama=big-model of office_bearers"),
+            "{}",
+            err
+        );
+        assert!(err.contains("ollama pull big-model"), "{}", err);
+
+        // Escalated to from every section
+        server.set_models(&["mock-model", "big-model"]);
+        let err = preflight(&ctx).await.unwrap_err().to_string();
+        assert!(
+            err.starts_with("Model openai=gpt-4.1 of company_details, "),
+            "{}",
+            err
+        );
+
+        server.set_models(&["mock-model", "big-model", "gpt-4.1"]);
+        preflight(&ctx).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_openai() {
+        let server = MockLlmServer::start().unwrap();
+        let client = Client::new();
+        let url = format!("{}/v1", server.url());
+        server.set_models(&["gpt-4.1-mini"]);
+
+        assert!(check_openai(&client, &url, "gpt-4.1-mini", Some("sk-test"))
+            .await
+            .is_ok());
+        assert!(check_openai(&client, &url, "gpt-5", Some("sk-test"))
+            .await
+            .is_err());
+        assert!(check_openai(&client, &url, "gpt-4.1-mini", None)
+            .await
+            .is_err());
+    }
+}
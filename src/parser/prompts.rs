@@ -0,0 +1,183 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Templates compiled into the binary, used when the template directory
+/// does not provide an override
+const BUILTIN_TEMPLATES: [(&str, &str); 11] = [
+    ("base", include_str!("../../prompts/base.txt")),
+    (
+        "company_details",
+        include_str!("../../prompts/company_details.txt"),
+    ),
+    (
+        "business_details",
+        include_str!("../../prompts/business_details.txt"),
+    ),
+    (
+        "stated_capital",
+        include_str!("../../prompts/stated_capital.txt"),
+    ),
+    (
+        "certificates",
+        include_str!("../../prompts/certificates.txt"),
+    ),
+    (
+        "office_bearers",
+        include_str!("../../prompts/office_bearers.txt"),
+    ),
+    (
+        "shareholders",
+        include_str!("../../prompts/shareholders.txt"),
+    ),
+    (
+        "annual_returns",
+        include_str!("../../prompts/annual_returns.txt"),
+    ),
+    (
+        "registration_fee",
+        include_str!("../../prompts/registration_fee.txt"),
+    ),
+    (
+        "balance_sheet",
+        include_str!("../../prompts/balance_sheet.txt"),
+    ),
+    (
+        "profit_and_loss",
+        include_str!("../../prompts/profit_and_loss.txt"),
+    ),
+];
+
+/// A prompt template with its version header stripped
+///
+/// Template files start with a `version: <id>` line followed by a `---`
+/// separator; the rest of the file is the template body.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: String,
+    pub body: String,
+}
+
+impl PromptTemplate {
+    pub fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let (header, body) = source
+            .split_once("\n---\n")
+            .ok_or_else(|| format!("template '{name}' is missing the '---' header separator"))?;
+
+        let version = header
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim() == "version")
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| format!("template '{name}' has no version"))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            version,
+            body: body.trim().to_string(),
+        })
+    }
+
+    /// Identifier stamped into the output, e.g. `office_bearers@1`
+    pub fn id(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+
+    /// [`id`](Self::id) and a hash of the body, e.g. `office_bearers@1#1a2b3c4d`,
+    /// so that an edit changes it even if the version was not bumped
+    pub fn fingerprint(&self) -> String {
+        let hash = format!("{:x}", Sha256::digest(self.body.as_bytes()));
+        format!("{}#{}", self.id(), &hash[..8])
+    }
+
+    /// Replace every `{{key}}` placeholder with its value.
+    ///
+    /// Values are substituted in the given order, so untrusted text (the
+    /// section content) should come last.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut out = self.body.clone();
+        for (key, value) in values {
+            out = out.replace(&format!("{{{{{key}}}}}"), value);
+        }
+        out
+    }
+}
+
+/// Set of prompt templates: built-in defaults overridden by files from a
+/// template directory
+pub struct PromptLibrary {
+    templates: HashMap<String, PromptTemplate>,
+}
+
+impl PromptLibrary {
+    /// Load the built-in templates, then override them with any
+    /// `<name>.txt` file found in `dir`. Invalid files are logged and ignored.
+    pub fn load(dir: &Path) -> Self {
+        let mut templates = HashMap::new();
+
+        for (name, source) in BUILTIN_TEMPLATES {
+            let template = PromptTemplate::parse(name, source).expect("invalid built-in template");
+            templates.insert(name.to_string(), template);
+        }
+
+        for name in BUILTIN_TEMPLATES.map(|(name, _)| name) {
+            let path = dir.join(format!("{name}.txt"));
+            let Ok(source) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            match PromptTemplate::parse(name, &source.replace("\r\n", "\n")) {
+                Ok(template) => {
+                    templates.insert(name.to_string(), template);
+                }
+                Err(e) => tracing::warn!("Ignoring {}: {}", path.display(), e),
+            }
+        }
+
+        Self { templates }
+    }
+
+    pub fn get(&self, name: &str) -> &PromptTemplate {
+        self.templates
+            .get(name)
+            .unwrap_or_else(|| panic!("unknown prompt template '{name}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template_header() {
+        let template = PromptTemplate::parse("demo", "version: 3\n---\nHello {{name}}\n").unwrap();
+        assert_eq!(template.version, "3");
+        assert_eq!(template.id(), "demo@3");
+        assert_eq!(template.render(&[("name", "world")]), "Hello world");
+
+        // An edit shows even without a version bump
+        let edited = PromptTemplate::parse("demo", "version: 3\n---\nHi {{name}}\n").unwrap();
+        assert!(template.fingerprint().starts_with("demo@3#"));
+        assert_ne!(edited.fingerprint(), template.fingerprint());
+    }
+
+    #[test]
+    fn test_parse_template_without_version_fails() {
+        assert!(PromptTemplate::parse("demo", "Hello").is_err());
+        assert!(PromptTemplate::parse("demo", "author: me\n---\nHello").is_err());
+    }
+
+    #[test]
+    fn test_directory_overrides_builtin() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("base.txt"), "version: 9\n---\n{{rules}}").unwrap();
+        std::fs::write(dir.path().join("shareholders.txt"), "no header").unwrap();
+
+        let library = PromptLibrary::load(dir.path());
+        assert_eq!(library.get("base").version, "9");
+        assert_eq!(library.get("shareholders").version, "2");
+        assert_eq!(library.get("office_bearers").version, "2");
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::api::LlmProvider;
+
+/// How requests are spread over the load-balanced providers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingStrategy {
+    /// Take turns
+    RoundRobin,
+    /// Prefer the provider with the fewest requests in flight
+    LeastBusy,
+}
+
+impl RoutingStrategy {
+    /// Parse the `LLM_ROUTING` setting (`round-robin` or `least-busy`)
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "least-busy" | "least_busy" => RoutingStrategy::LeastBusy,
+            _ => RoutingStrategy::RoundRobin,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoutingStrategy::RoundRobin => "round-robin",
+            RoutingStrategy::LeastBusy => "least-busy",
+        }
+    }
+}
+
+/// Health of one provider during the run
+#[derive(Default)]
+struct ProviderHealth {
+    in_flight: AtomicUsize,
+    consecutive_failures: AtomicU32,
+    down_until: Mutex<Option<Instant>>,
+    requests: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// Request counts of a provider, for the end-of-run summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderStats {
+    pub label: String,
+    pub requests: u64,
+    pub failures: u64,
+    pub available: bool,
+}
+
+/// Spreads requests over the configured providers and keeps track of which
+/// ones are failing.
+///
+/// A provider that fails `max_failures` times in a row is taken out of
+/// rotation for `cooldown`; a success puts it back. Fallback providers are
+/// only tried after every load-balanced one.
+pub struct ProviderRouter {
+    providers: Vec<LlmProvider>,
+    health: Vec<ProviderHealth>,
+    strategy: RoutingStrategy,
+    max_failures: u32,
+    cooldown: Duration,
+    next: AtomicUsize,
+}
+
+/// Marks a request as in flight on a provider until dropped
+pub struct InFlight<'a> {
+    health: &'a ProviderHealth,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.health.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ProviderRouter {
+    pub fn new(
+        providers: Vec<LlmProvider>,
+        strategy: RoutingStrategy,
+        max_failures: u32,
+        cooldown: Duration,
+    ) -> Self {
+        let health = providers
+            .iter()
+            .map(|_| ProviderHealth::default())
+            .collect();
+        Self {
+            providers,
+            health,
+            strategy,
+            max_failures: max_failures.max(1),
+            cooldown,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn providers(&self) -> &[LlmProvider] {
+        &self.providers
+    }
+
+    /// Whether a provider is in rotation (not cooling down after failures)
+    pub fn is_available(&self, index: usize) -> bool {
+        let mut down_until = self.health[index].down_until.lock().unwrap();
+        match *down_until {
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                // Cooldown over: give it another chance
+                *down_until = None;
+                self.health[index]
+                    .consecutive_failures
+                    .store(0, Ordering::SeqCst);
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Order in which providers should be tried for the next request:
+    /// available load-balanced providers by the routing strategy, then
+    /// available fallbacks in order, then providers cooling down as a last resort
+    pub fn plan(&self) -> Vec<usize> {
+        let (available, cooling): (Vec<usize>, Vec<usize>) =
+            (0..self.providers.len()).partition(|&i| self.is_available(i));
+        let (mut pool, fallbacks): (Vec<usize>, Vec<usize>) = available
+            .into_iter()
+            .partition(|&i| !self.providers[i].fallback);
+
+        if !pool.is_empty() {
+            let start = self.next.fetch_add(1, Ordering::SeqCst) % pool.len();
+            pool.rotate_left(start);
+            if self.strategy == RoutingStrategy::LeastBusy {
+                // Stable sort keeps the rotation as the tie-breaker
+                pool.sort_by_key(|&i| self.health[i].in_flight.load(Ordering::SeqCst));
+            }
+        }
+
+        pool.into_iter().chain(fallbacks).chain(cooling).collect()
+    }
+
+    /// Count a request as in flight on a provider while the guard lives
+    pub fn start(&self, index: usize) -> InFlight<'_> {
+        let health = &self.health[index];
+        health.in_flight.fetch_add(1, Ordering::SeqCst);
+        health.requests.fetch_add(1, Ordering::SeqCst);
+        InFlight { health }
+    }
+
+    pub fn record_success(&self, index: usize) {
+        self.health[index]
+            .consecutive_failures
+            .store(0, Ordering::SeqCst);
+    }
+
+    /// Count a failed request, taking the provider out of rotation once it
+    /// has failed `max_failures` times in a row
+    pub fn record_failure(&self, index: usize) {
+        let health = &self.health[index];
+        health.failures.fetch_add(1, Ordering::SeqCst);
+        let failures = health.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= self.max_failures {
+            self.mark_down(index);
+        }
+    }
+
+    /// Take a provider out of rotation for the cooldown period
+    pub fn mark_down(&self, index: usize) {
+        let mut down_until = self.health[index].down_until.lock().unwrap();
+        if down_until.is_none() {
+            tracing::warn!(
+                "LLM provider {} taken out of rotation for {:?}",
+                self.providers[index].label(),
+                self.cooldown
+            );
+        }
+        *down_until = Some(Instant::now() + self.cooldown);
+    }
+
+    pub fn stats(&self) -> Vec<ProviderStats> {
+        (0..self.providers.len())
+            .map(|i| ProviderStats {
+                label: self.providers[i].label(),
+                requests: self.health[i].requests.load(Ordering::SeqCst),
+                failures: self.health[i].failures.load(Ordering::SeqCst),
+                available: self.is_available(i),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::api::LlmBackend;
+
+    fn provider(url: &str, fallback: bool) -> LlmProvider {
+        LlmProvider {
+            backend: LlmBackend::Ollama,
+            url: url.to_string(),
+            model: "m".to_string(),
+            api_key: None,
+            fallback,
+        }
+    }
+
+    fn router(strategy: RoutingStrategy) -> ProviderRouter {
+        ProviderRouter::new(
+            vec![
+                provider("h1", false),
+                provider("h2", false),
+                provider("cloud", true),
+            ],
+            strategy,
+            2,
+            Duration::from_secs(60),
+        )
+    }
+
+    #[test]
+    fn test_round_robin_with_fallback_last() {
+        let router = router(RoutingStrategy::RoundRobin);
+        assert_eq!(router.plan(), vec![0, 1, 2]);
+        assert_eq!(router.plan(), vec![1, 0, 2]);
+        assert_eq!(router.plan(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_least_busy() {
+        let router = router(RoutingStrategy::LeastBusy);
+        let busy = router.start(0);
+        assert_eq!(router.plan()[0], 1);
+        assert_eq!(router.plan()[0], 1);
+        drop(busy);
+        assert_eq!(router.plan()[..2], [0, 1]);
+    }
+
+    #[test]
+    fn test_failing_provider_leaves_rotation() {
+        let router = router(RoutingStrategy::RoundRobin);
+        router.record_failure(0);
+        assert!(router.is_available(0));
+        router.record_failure(0);
+        assert!(!router.is_available(0));
+
+        // Still tried, but only after every healthy provider
+        assert_eq!(router.plan(), vec![1, 2, 0]);
+
+        router.record_failure(1);
+        router.record_success(1);
+        router.record_failure(1);
+        assert!(router.is_available(1));
+
+        let stats = router.stats();
+        assert_eq!(stats[0].failures, 2);
+        assert!(!stats[0].available);
+    }
+
+    #[test]
+    fn test_cooldown_expires() {
+        let router = ProviderRouter::new(
+            vec![provider("h1", false)],
+            RoutingStrategy::RoundRobin,
+            1,
+            Duration::ZERO,
+        );
+        router.record_failure(0);
+        assert!(router.is_available(0));
+    }
+}
@@ -1,7 +1,25 @@
 use std::collections::HashSet;
 
+use crate::parser::ollama::SectionParser;
 use crate::ALL_SECTIONS;
 
+/// Look up a section by index (`4`), heading (`Office Bearers`, any case)
+/// or prompt template name (`office_bearers`)
+///
+/// # Returns
+/// * The section's index in `ALL_SECTIONS`, or `None` if no section matches
+pub fn find_section(name: &str) -> Option<usize> {
+    let name = name.trim();
+    if let Ok(index) = name.parse::<usize>() {
+        return (index < ALL_SECTIONS.len()).then_some(index);
+    }
+    (0..ALL_SECTIONS.len()).find(|&index| {
+        ALL_SECTIONS[index].eq_ignore_ascii_case(name)
+            || SectionParser::from_section_index(index)
+                .is_some_and(|parser| parser.template_name() == name.to_lowercase())
+    })
+}
+
 /// Extracts the content of a given section from a PDF's text representation.
 ///
 /// # Arguments
@@ -82,4 +100,14 @@ mod tests {
         assert!(result.contains("Some company info"));
         assert!(!result.contains("Business Details"));
     }
+
+    #[test]
+    fn test_find_section() {
+        assert_eq!(find_section("4"), Some(4));
+        assert_eq!(find_section("office bearers"), Some(4));
+        assert_eq!(find_section("Balance Sheet"), Some(11));
+        assert_eq!(find_section("shareholders"), Some(5));
+        assert_eq!(find_section("17"), None);
+        assert_eq!(find_section("directors"), None);
+    }
 }
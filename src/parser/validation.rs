@@ -0,0 +1,107 @@
+use serde_json::Value;
+
+use crate::parser::grounding::leaves;
+
+/// Whether a value looks like a date: a 4-digit year with a day and month,
+/// e.g. `01/02/2003`, `2003-02-01` or `1 Feb 2003`
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value
+        .split(|c: char| c == '/' || c == '-' || c == '.' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    parts.len() == 3
+        && parts.iter().any(|p| {
+            p.len() == 4
+                && p.parse::<u32>()
+                    .is_ok_and(|year| (1800..=2100).contains(&year))
+        })
+        && parts
+            .iter()
+            .all(|p| p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// File numbers are a letter followed by digits, e.g. `C12` or `P15`
+fn is_file_no(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && value.len() > 1
+        && chars.all(|c| c.is_ascii_digit())
+}
+
+/// Share counts are digits, possibly with thousands separators
+fn is_count(value: &str) -> bool {
+    value.chars().any(|c| c.is_ascii_digit())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ',' || c == ' ')
+}
+
+/// Country names are words only
+fn is_country(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c.is_alphabetic() || c == ' ' || c == '(' || c == ')' || c == '-')
+}
+
+/// Check a single field by name. Empty values are always valid.
+pub fn is_valid_field(field: &str, value: &str) -> bool {
+    let value = value.trim();
+    if value.is_empty() {
+        return true;
+    }
+
+    match field {
+        "orgFileNo" => is_file_no(value),
+        "numShares" => is_count(value),
+        "country" => is_country(value),
+        f if f.ends_with("Date") || f.ends_with("date") || f.ends_with("Dt") => is_date(value),
+        _ => true,
+    }
+}
+
+/// Check every field of an extracted value against the format rules
+///
+/// # Returns
+/// * JSON pointers of the fields that fail their rule
+pub fn invalid_fields(value: &Value) -> Vec<String> {
+    leaves(value)
+        .into_iter()
+        .filter_map(|(path, leaf)| {
+            let field = path.rsplit('/').next().unwrap_or_default();
+            let valid = leaf.as_str().is_none_or(|s| is_valid_field(field, s));
+            (!valid).then_some(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_field_rules() {
+        assert!(is_valid_field("appointedDate", "01/02/2003"));
+        assert!(is_valid_field("orgIncorpDate", "1 Feb 2003"));
+        assert!(!is_valid_field("appointedDate", "PORT LOUIS"));
+        assert!(is_valid_field("orgFileNo", "C4235"));
+        assert!(!is_valid_field("orgFileNo", "ACME LTD"));
+        assert!(is_valid_field("numShares", "1,000,000"));
+        assert!(!is_valid_field("country", "PORT LOUIS 11324"));
+        assert!(is_valid_field("name", "anything"));
+        assert!(is_valid_field("appointedDate", ""));
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let value = json!({"officeBearers": [
+            {"name": "JOHN", "appointedDate": "01/01/2020"},
+            {"name": "JANE", "appointedDate": "yesterday"}
+        ]});
+        assert_eq!(
+            invalid_fields(&value),
+            vec!["/officeBearers/1/appointedDate"]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Output files are written to a temporary file next to them and renamed
+//! into place, so that an interrupted run never leaves a truncated file.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Suffix of temporary files; they start with a dot, so the frontend's
+/// `*.json` glob never picks one up
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Distinguishes temporary files of concurrent writes to the same path
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(
+        ".{}.{}.{}{}",
+        name,
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed),
+        TEMP_SUFFIX
+    ))
+}
+
+/// Write a file in full or not at all
+///
+/// The contents are written and flushed to disk under a temporary name in
+/// the same directory, then renamed over `path`, which replaces it in one step.
+pub fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let temp = temp_path(path);
+
+    let result = std::fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Remove the temporary files an interrupted run left in `dir`
+///
+/// Only files last modified before `before` are removed: a run started on the
+/// same directory in the meantime may be writing the others right now.
+///
+/// # Arguments
+/// * `dir` - Directory to clean; a missing one has nothing to remove
+/// * `before` - Start of the current run
+///
+/// # Returns
+/// * Number of files removed
+pub fn remove_temp_files(dir: impl AsRef<Path>, before: SystemTime) -> std::io::Result<usize> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with('.') || !name.ends_with(TEMP_SUFFIX) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_file() && metadata.modified()? < before {
+            match std::fs::remove_file(entry.path()) {
+                Ok(()) => removed += 1,
+                // Renamed into place or removed by its writer meanwhile
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("acme.json");
+        std::fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        // Nothing but the file itself is left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        std::fs::write(temp_path(&path), "{\"partial").unwrap();
+        let started = SystemTime::now() + std::time::Duration::from_secs(1);
+        assert_eq!(remove_temp_files(dir.path(), started).unwrap(), 1);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_temp_files_of_a_later_run_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let started = SystemTime::now() - std::time::Duration::from_secs(60);
+        let live = temp_path(&dir.path().join("acme.json"));
+        std::fs::write(&live, "{\"partial").unwrap();
+
+        assert_eq!(remove_temp_files(dir.path(), started).unwrap(), 0);
+        assert!(live.exists());
+        assert_eq!(
+            remove_temp_files(dir.path().join("missing"), started).unwrap(),
+            0
+        );
+    }
+}
@@ -1,12 +1,28 @@
-use reqwest::Client;
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::path::Path;
-use std::time::{Duration, Instant};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Semaphore;
 
-use crate::parser::ollama::SectionParser;
+use crate::config::Config;
+use crate::models::api::TokenUsage;
+use crate::parser::confidence::low_confidence_records;
+use crate::parser::context::LlmContext;
+use crate::parser::ollama::{LlmExchange, ParsedSection, SectionParser};
 use crate::parser::pdf::get_text_from_pdf;
+use crate::parser::preflight::preflight;
 use crate::parser::section::extract_section;
+use crate::parser::validation::invalid_fields;
+use crate::processor::atomic::{remove_temp_files, write_atomic};
+use crate::processor::checkpoint::SectionCheckpoints;
+use crate::processor::journal::RunJournal;
+use crate::processor::manifest::{
+    scoring, ExpectedFingerprint, FileStatus, Fingerprint, Manifest, SectionFingerprint, STATE_DIR,
+};
 
 /// Simple lightweight timer
 struct Timer<'a> {
@@ -29,30 +45,47 @@ impl<'a> Timer<'a> {
     }
 }
 
-/// Helper function to convert parsed JSON to the appropriate output key and value
-fn output_key_and_value(section_index: usize, json: Value) -> Option<(String, Value)> {
-    let key = match section_index {
-        0 => "companyDetails",
-        1 => "businessDetails",
-        4 => "officeBearers",
-        5 => "shareHolders",
-        _ => return None,
-    };
+/// Key under which a section is stored in the output JSON
+fn output_key(section_index: usize) -> Option<&'static str> {
+    match section_index {
+        0 => Some("companyDetails"),
+        1 => Some("businessDetails"),
+        2 => Some("statedCapitals"),
+        3 => Some("certificates"),
+        4 => Some("officeBearers"),
+        5 => Some("shareHolders"),
+        7 => Some("annualReturns"),
+        10 => Some("profitAndLoss"),
+        11 => Some("balanceSheet"),
+        15 => Some("registrationFee"),
+        _ => None,
+    }
+}
+
+/// Helper function to convert parsed JSON to the appropriate output key and
+/// value. List sections are stored as the bare list.
+pub(crate) fn output_key_and_value(section_index: usize, json: Value) -> Option<(String, Value)> {
+    let key = output_key(section_index)?;
+    let list_key = SectionParser::from_section_index(section_index).and_then(|p| p.list_key());
 
-    let value = match json {
-        Value::Object(obj) if obj.len() == 1 => {
-            // Extract the only value safely
-            let (_, v) = obj.into_iter().next().unwrap();
-            v
+    let value = match (list_key, json) {
+        (Some(list_key), Value::Object(mut obj))
+            if obj.len() == 1 && obj.contains_key(list_key) =>
+        {
+            obj.remove(list_key).unwrap()
         }
-        other => other,
+        (_, other) => other,
     };
 
     Some((key.to_string(), value))
 }
 
 /// Build markdown representation of extracted sections
-fn build_markdown_for_pdf(pdf_name: &str, pdf_text: &str, sections_to_parse: &[usize]) -> String {
+pub fn build_markdown_for_pdf(
+    pdf_name: &str,
+    pdf_text: &str,
+    sections_to_parse: &[usize],
+) -> String {
     let mut md = String::new();
 
     md.push_str(&format!("# Extracted Sections from `{}`\n\n", pdf_name));
@@ -81,172 +114,820 @@ fn format_duration(d: Duration) -> String {
     format!("{mins}m {secs}s")
 }
 
-fn already_processed(output_dir: &str, pdf_filename: &str) -> bool {
-    let path = format!("{}/{}.json", output_dir, pdf_filename);
-    std::path::Path::new(&path).exists()
+/// Sections extracted from every file
+pub const DEFAULT_SECTIONS: [usize; 3] = [0, 1, 4];
+
+/// Which files of the input directory are processed, and how
+#[derive(Debug, Clone)]
+pub struct ProcessOptions {
+    /// Indices of the sections to extract (see [`crate::ALL_SECTIONS`])
+    pub sections: Vec<usize>,
+    /// Pattern that paths relative to the input directory must match
+    pub glob: String,
+    /// Also look for files in subdirectories
+    pub recursive: bool,
+    /// Process files whose output is up to date
+    pub force: bool,
 }
 
-/// Process all PDFs in a directory and save parsed sections to JSON files
+impl Default for ProcessOptions {
+    fn default() -> Self {
+        Self {
+            sections: DEFAULT_SECTIONS.to_vec(),
+            glob: "*.pdf".to_string(),
+            recursive: false,
+            force: false,
+        }
+    }
+}
+
+/// PDF files of a directory matching `glob`, in path order
 ///
-/// # Arguments
-/// * `input_dir` - Directory containing PDF files to process
-/// * `output_dir` - Directory where JSON output files will be saved
-/// * `markdown_dir` - Directory where markdown files will be saved
+/// Output files are named after the file stem, so two files with the same
+/// stem (in different subdirectories) are an error rather than one
+/// overwriting the other's output.
 ///
-/// # Returns
-/// * `Result<(), Box<dyn Error>>` - Success or error
-pub async fn process_pdfs_in_directory(
+/// # Arguments
+/// * `input_dir` - Directory to list
+/// * `glob` - Pattern matched against the path relative to `input_dir`
+/// * `recursive` - Also list subdirectories
+pub(crate) fn list_pdfs(
     input_dir: &str,
-    output_dir: &str,
-) -> Result<(), Box<dyn Error>> {
-    let debugging = std::env::var("DEBUGGING").ok().as_deref() == Some("true");
-    let debug_markdown_dir = "output_markdown";
-    let client = Client::new();
+    glob: &str,
+    recursive: bool,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let pattern = glob::Pattern::new(glob)?;
+    let root = Path::new(input_dir);
+    let mut entries = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
 
-    std::fs::create_dir_all(output_dir)?;
-    if debugging {
-        std::fs::create_dir_all(debug_markdown_dir)?;
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)?.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.is_dir() {
+                if recursive {
+                    dirs.push(path);
+                }
+            } else if is_listed(root, &path, &pattern, recursive) {
+                entries.push(path);
+            }
+        }
     }
+    entries.sort();
 
-    let sections_to_parse = [0, 1, 4];
-
-    let entries: Vec<_> = std::fs::read_dir(input_dir)?
-        .filter_map(Result::ok)
-        .map(|e| e.path())
-        .filter(|p| p.extension().map_or(false, |e| e == "pdf"))
+    let mut by_stem: BTreeMap<_, Vec<&PathBuf>> = BTreeMap::new();
+    for path in &entries {
+        by_stem.entry(path.file_stem()).or_default().push(path);
+    }
+    let clashes: Vec<String> = by_stem
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|paths| {
+            paths
+                .iter()
+                .map(|p| p.strip_prefix(root).unwrap_or(p).display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
         .collect();
+    if !clashes.is_empty() {
+        return Err(format!(
+            "PDFs with the same name would write the same output file: {}; rename them or narrow --glob",
+            clashes.join("; ")
+        )
+        .into());
+    }
+    Ok(entries)
+}
 
-    let total = entries.len();
-    let start = Instant::now();
-    let mut completed = 0usize;
+/// Whether `path` is a PDF that [`list_pdfs`] would list for `root`
+pub(crate) fn is_listed(
+    root: &Path,
+    path: &Path,
+    pattern: &glob::Pattern,
+    recursive: bool,
+) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    path.extension().is_some_and(|e| e == "pdf")
+        && (recursive || relative.components().count() == 1)
+        && pattern.matches_path(relative)
+}
 
-    // Aggregate timers (only used if debugging)
-    let mut time_pdf_extract = Duration::ZERO;
-    let mut time_section_extract = Duration::ZERO;
-    let mut time_llm_parse = Duration::ZERO;
-    let mut time_json_write = Duration::ZERO;
-    let mut time_markdown_write = Duration::ZERO;
-
-    for path in entries {
-        let file_timer = if debugging {
-            Some(Timer::new("TOTAL FILE"))
-        } else {
-            None
-        };
+/// Time spent in each processing stage (only collected if debugging)
+#[derive(Default)]
+pub(crate) struct StageTimings {
+    pdf_extract: Duration,
+    section_extract: Duration,
+    llm_parse: Duration,
+    json_write: Duration,
+    markdown_write: Duration,
+}
 
-        let pdf_path = path.to_str().unwrap();
-        let pdf_filename = path.file_stem().unwrap().to_str().unwrap();
+impl std::ops::AddAssign for StageTimings {
+    fn add_assign(&mut self, other: Self) {
+        self.pdf_extract += other.pdf_extract;
+        self.section_extract += other.section_extract;
+        self.llm_parse += other.llm_parse;
+        self.json_write += other.json_write;
+        self.markdown_write += other.markdown_write;
+    }
+}
 
-        if already_processed(output_dir, pdf_filename) {
-            tracing::info!("Skipping {} (checkpoint hit)", pdf_filename);
-            completed += 1;
-            continue;
-        }
+/// Result of a single section parse, reported once the whole file is done so
+/// that log output does not depend on completion order
+pub(crate) struct SectionOutcome {
+    pub(crate) section_index: usize,
+    pub(crate) section_name: &'static str,
+    /// `None` if the section is empty or has no parser
+    pub(crate) result: Option<Result<ParsedSection, String>>,
+    /// Taken from a checkpoint of an earlier run instead of being parsed
+    pub(crate) reused: bool,
+    /// Requests sent for the section, for the debug trace
+    pub(crate) trace: Vec<LlmExchange>,
+    pub(crate) latency: Duration,
+}
+
+/// Extract PDF text on the blocking pool so that in-flight LLM requests of
+/// other files are not stalled
+pub async fn extract_pdf_text(pdf_path: impl AsRef<Path>) -> Result<String, Box<dyn Error>> {
+    let pdf_path = pdf_path.as_ref().to_path_buf();
+    Ok(tokio::task::spawn_blocking(move || get_text_from_pdf(&pdf_path)).await?)
+}
 
-        tracing::info!("Processing {}", pdf_filename);
+/// Parsed sections of one PDF along with what it took to produce them
+pub(crate) struct ParsedSections {
+    pub(crate) data: serde_json::Map<String, Value>,
+    /// Tokens spent in this run, not counting sections taken from checkpoints
+    pub(crate) usage: TokenUsage,
+    /// Sections that could not be parsed, also listed under `errors`
+    pub(crate) failed: Vec<&'static str>,
+    pub(crate) timings: StageTimings,
+    /// Records below `MIN_CONFIDENCE`, to be appended to the review queue
+    pub(crate) review: Vec<Value>,
+    /// Per-section requests, raw replies and final values, written in debug mode
+    pub(crate) trace: Vec<Value>,
+    /// What each section was extracted with, `None` for empty sections;
+    /// failed sections are left out
+    pub(crate) fingerprints: BTreeMap<String, Option<SectionFingerprint>>,
+}
 
-        // PDF extraction timing
-        let pdf_text = if debugging {
-            let t = Timer::new("PDF text extraction");
-            let text = get_text_from_pdf(pdf_path);
-            time_pdf_extract += t.stop();
-            text
-        } else {
-            get_text_from_pdf(pdf_path)
-        };
+/// Parse the requested sections of one PDF, at most `section_concurrency` at a time
+///
+/// # Returns
+/// * The sections assembled by [`assemble_sections`], plus the stage timings
+async fn parse_sections(
+    ctx: &LlmContext,
+    pdf_text: &str,
+    sections_to_parse: &[usize],
+    section_concurrency: usize,
+    checkpoints: Option<&SectionCheckpoints>,
+) -> ParsedSections {
+    let semaphore = Semaphore::new(section_concurrency);
+    let mut timings = StageTimings::default();
 
-        let mut pdf_data = serde_json::Map::new();
-        pdf_data.insert("filename".into(), Value::String(pdf_filename.into()));
+    let section_start = Instant::now();
+    let sections: Vec<(usize, String)> = sections_to_parse
+        .iter()
+        .map(|&idx| (idx, extract_section(idx, pdf_text)))
+        .collect();
+    timings.section_extract = section_start.elapsed();
 
-        for &section_index in &sections_to_parse {
-            let section_name = SectionParser::section_name(section_index);
+    let llm_start = Instant::now();
+    let outcomes = join_all(sections.iter().map(|(section_index, section_text)| {
+        let semaphore = &semaphore;
+        async move {
+            let section_name = SectionParser::section_name(*section_index);
 
-            // Section extraction timing
-            let section_text = if debugging {
-                let t = Timer::new("Section extraction");
-                let text = extract_section(section_index, &pdf_text);
-                time_section_extract += t.stop();
-                text
-            } else {
-                extract_section(section_index, &pdf_text)
+            let parser = match SectionParser::from_section_index(*section_index) {
+                Some(parser) if !section_text.trim().is_empty() => parser,
+                _ => {
+                    return SectionOutcome {
+                        section_index: *section_index,
+                        section_name,
+                        result: None,
+                        reused: false,
+                        trace: Vec::new(),
+                        latency: Duration::ZERO,
+                    }
+                }
             };
 
-            if section_text.trim().is_empty() {
-                continue;
+            if let Some(parsed) = checkpoints.and_then(|c| c.load(*section_index)) {
+                return SectionOutcome {
+                    section_index: *section_index,
+                    section_name,
+                    result: Some(Ok(parsed)),
+                    reused: true,
+                    trace: Vec::new(),
+                    latency: Duration::ZERO,
+                };
             }
 
-            tracing::info!("  Parsing {}", section_name);
+            let _permit = semaphore.acquire().await.expect("semaphore closed");
+            let mut trace = Vec::new();
+            let started = Instant::now();
+            let result = parser
+                .parse_traced(ctx, section_text, section_name, &mut trace)
+                .await
+                .map_err(|e| e.to_string());
 
-            if let Some(parser) = SectionParser::from_section_index(section_index) {
-                match parser.parse(&client, &section_text, section_name).await {
-                    Ok(json) => {
-                        if debugging {
-                            let t = Timer::new("LLM parse");
-                            time_llm_parse += t.stop();
-                        }
-                        if let Some((key, value)) = output_key_and_value(section_index, json) {
-                            pdf_data.insert(key, value);
+            // Saved right away, so that an interrupted file keeps its finished sections
+            if let Some(checkpoints) = checkpoints {
+                match &result {
+                    Ok(parsed) => {
+                        if let Err(e) = checkpoints.save(*section_index, parsed) {
+                            tracing::warn!("  Could not save {} checkpoint: {}", section_name, e);
                         }
                     }
-                    Err(e) => {
-                        if debugging {
-                            let t = Timer::new("LLM parse");
-                            time_llm_parse += t.stop();
-                        }
-                        tracing::warn!("  {} parse error: {}", section_name, e);
-                    }
+                    Err(_) => checkpoints.remove(*section_index),
                 }
             }
+
+            SectionOutcome {
+                section_index: *section_index,
+                section_name,
+                result: Some(result),
+                reused: false,
+                trace,
+                latency: started.elapsed(),
+            }
         }
+    }))
+    .await;
+    timings.llm_parse = llm_start.elapsed();
 
-        // JSON write timing
-        if debugging {
-            let t = Timer::new("JSON write");
-            let json_path = format!("{}/{}.json", output_dir, pdf_filename);
-            std::fs::write(&json_path, serde_json::to_string_pretty(&pdf_data)?)?;
-            time_json_write += t.stop();
-        } else {
-            let json_path = format!("{}/{}.json", output_dir, pdf_filename);
-            std::fs::write(&json_path, serde_json::to_string_pretty(&pdf_data)?)?;
+    let mut parsed = assemble_sections(&ctx.config, sections_to_parse, outcomes);
+    parsed.timings = timings;
+    parsed
+}
+
+/// Build the output map of one PDF from the outcome of each of its sections
+///
+/// # Arguments
+/// * `config` - Configuration deciding which records go to review
+/// * `sections_to_parse` - Requested sections, in the order failures are listed
+/// * `outcomes` - Outcome of each section
+///
+/// # Returns
+/// * The output map (without `filename`), including a `tokenUsage` entry with
+///   per-section and per-file token counts, a `promptVersions` entry naming
+///   the templates behind each section and, if any, an `ungroundedFields`
+///   entry listing values not found in their section, an `escalations` entry
+///   naming the model each escalated section was re-extracted with, an
+///   `errors` entry giving the error of each section that failed, and a
+///   `confidence` entry scoring every field. Stage timings are left at zero.
+pub(crate) fn assemble_sections(
+    config: &Config,
+    sections_to_parse: &[usize],
+    outcomes: Vec<SectionOutcome>,
+) -> ParsedSections {
+    let scoring = scoring(&config.processing);
+    let mut pdf_data = serde_json::Map::new();
+    let mut file_usage = TokenUsage::default();
+    let mut run_usage = TokenUsage::default();
+    let mut errors = serde_json::Map::new();
+    let mut section_usage = serde_json::Map::new();
+    let mut prompt_versions = serde_json::Map::new();
+    let mut ungrounded = serde_json::Map::new();
+    let mut escalations = serde_json::Map::new();
+    let mut confidence = serde_json::Map::new();
+    let mut review = Vec::new();
+    let mut trace = Vec::new();
+    let mut fingerprints = BTreeMap::new();
+    for outcome in outcomes {
+        let section_index = &outcome.section_index;
+        let parser = SectionParser::from_section_index(*section_index);
+        if outcome.result.is_some() {
+            trace.push(serde_json::json!({
+                "section": outcome.section_name,
+                "latencyMs": outcome.latency.as_millis() as u64,
+                "value": outcome.result.as_ref().and_then(|r| r.as_ref().ok()).map(|p| &p.value),
+                "error": outcome.result.as_ref().and_then(|r| r.as_ref().err()),
+                "checkpoint": outcome.reused,
+                "requests": outcome.trace,
+            }));
+        }
+        match outcome.result {
+            None => {
+                if parser.is_some() {
+                    fingerprints.insert(outcome.section_name.to_string(), None);
+                }
+            }
+            Some(Ok(parsed)) => {
+                if outcome.reused {
+                    tracing::info!("  Reused {} from checkpoint", outcome.section_name);
+                } else {
+                    tracing::info!(
+                        "  Parsed {} ({} prompt + {} completion tokens)",
+                        outcome.section_name,
+                        parsed.usage.prompt_tokens,
+                        parsed.usage.completion_tokens
+                    );
+                    run_usage += parsed.usage;
+                }
+                file_usage += parsed.usage;
+                if let Some(parser) = &parser {
+                    fingerprints.insert(
+                        outcome.section_name.to_string(),
+                        Some(SectionFingerprint::of(parser, &parsed, &scoring)),
+                    );
+                }
+                prompt_versions.insert(
+                    outcome.section_name.to_string(),
+                    Value::String(parsed.prompt_version),
+                );
+                if !parsed.ungrounded.is_empty() {
+                    ungrounded.insert(
+                        outcome.section_name.to_string(),
+                        serde_json::to_value(parsed.ungrounded).unwrap(),
+                    );
+                }
+                if let Some(model) = parsed.escalated_to {
+                    escalations.insert(outcome.section_name.to_string(), Value::String(model));
+                }
+                section_usage.insert(
+                    outcome.section_name.to_string(),
+                    serde_json::to_value(parsed.usage).unwrap(),
+                );
+
+                // Reused sections were queued for review when they were parsed
+                let min_confidence = config.processing.min_confidence;
+                if min_confidence > 0.0 && !outcome.reused {
+                    let list_key = parser.as_ref().and_then(|parser| parser.list_key());
+                    for record in
+                        low_confidence_records(&parsed.confidence, list_key, min_confidence)
+                    {
+                        review.push(serde_json::json!({
+                            "section": outcome.section_name,
+                            "pointer": record.pointer,
+                            "minConfidence": record.min_confidence,
+                            "record": parsed.value.pointer(&record.pointer),
+                        }));
+                    }
+                }
+                confidence.insert(
+                    outcome.section_name.to_string(),
+                    serde_json::to_value(parsed.confidence).unwrap(),
+                );
+
+                if let Some((key, value)) = output_key_and_value(*section_index, parsed.value) {
+                    pdf_data.insert(key, value);
+                }
+            }
+            Some(Err(e)) => {
+                tracing::warn!("  {} parse error: {}", outcome.section_name, e);
+                errors.insert(outcome.section_name.to_string(), Value::String(e));
+            }
         }
+    }
+
+    let mut usage = serde_json::to_value(file_usage).unwrap();
+    usage["sections"] = Value::Object(section_usage);
+    pdf_data.insert("tokenUsage".into(), usage);
+    pdf_data.insert("promptVersions".into(), Value::Object(prompt_versions));
+    if !ungrounded.is_empty() {
+        pdf_data.insert("ungroundedFields".into(), Value::Object(ungrounded));
+    }
+    if !escalations.is_empty() {
+        pdf_data.insert("escalations".into(), Value::Object(escalations));
+    }
+    pdf_data.insert("confidence".into(), Value::Object(confidence));
+    let failed = sections_to_parse
+        .iter()
+        .map(|&idx| SectionParser::section_name(idx))
+        .filter(|name| errors.contains_key(*name))
+        .collect();
+    if !errors.is_empty() {
+        pdf_data.insert("errors".into(), Value::Object(errors));
+    }
+
+    ParsedSections {
+        data: pdf_data,
+        usage: run_usage,
+        failed,
+        timings: StageTimings::default(),
+        review,
+        trace,
+        fingerprints,
+    }
+}
+
+/// Append a file's low-confidence records to `<output_dir>/review_queue.jsonl`
+pub(crate) fn append_to_review_queue(
+    output_dir: &str,
+    pdf_filename: &str,
+    records: Vec<Value>,
+) -> Result<(), Box<dyn Error>> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    tracing::warn!(
+        "  {} low-confidence records of {} sent to review",
+        records.len(),
+        pdf_filename
+    );
+
+    let mut lines = String::new();
+    for mut record in records {
+        record["filename"] = Value::String(pdf_filename.to_string());
+        lines.push_str(&serde_json::to_string(&record)?);
+        lines.push('\n');
+    }
+
+    // A single write per file keeps lines of concurrent files from interleaving
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(format!("{}/review_queue.jsonl", output_dir))?;
+    file.write_all(lines.as_bytes())?;
+    Ok(())
+}
+
+/// Directory the markdown and trace of each file are written to if debugging
+pub(crate) const DEBUG_MARKDOWN_DIR: &str = "output_markdown";
+
+/// Remove the temporary files interrupted runs left in every directory
+/// written to atomically: the output, the run state with each file's manifest
+/// entry and saved sections, and the debug markdown
+///
+/// # Arguments
+/// * `output_dir` - Output directory of the run
+/// * `started` - Start of the run; newer files may belong to a concurrent one
+pub(crate) fn remove_stale_temp_files(
+    output_dir: &str,
+    started: SystemTime,
+) -> std::io::Result<()> {
+    let state_dir = Path::new(output_dir).join(STATE_DIR);
+    let mut dirs = vec![
+        PathBuf::from(output_dir),
+        state_dir.clone(),
+        state_dir.join("manifest"),
+        PathBuf::from(DEBUG_MARKDOWN_DIR),
+    ];
+    if let Ok(entries) = std::fs::read_dir(state_dir.join("sections")) {
+        dirs.extend(
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir()),
+        );
+    }
+
+    let mut removed = 0;
+    for dir in dirs {
+        removed += remove_temp_files(dir, started)?;
+    }
+    if removed > 0 {
+        tracing::info!(
+            "Removed {} temporary files left by an interrupted run",
+            removed
+        );
+    }
+    Ok(())
+}
+
+/// Output of one PDF, before it is written
+struct ProcessedFile {
+    /// Output map, starting with `filename`
+    data: serde_json::Map<String, Value>,
+    usage: TokenUsage,
+    failed: Vec<&'static str>,
+    timings: StageTimings,
+    review: Vec<Value>,
+    fingerprints: BTreeMap<String, Option<SectionFingerprint>>,
+}
 
-        // Markdown write timing
-        if debugging {
-            let t = Timer::new("Markdown write");
-            let markdown = build_markdown_for_pdf(pdf_filename, &pdf_text, &sections_to_parse);
-            let md_path = format!("{}/{}.md", debug_markdown_dir, pdf_filename);
-            std::fs::write(&md_path, markdown)?;
-            time_markdown_write += t.stop();
+/// Extract and parse the sections of one PDF, writing its markdown and
+/// trace to `output_markdown/` if debugging
+///
+/// Sections are taken from `checkpoints` when they are up to date there,
+/// and saved to it as they are parsed.
+async fn process_file(
+    ctx: &LlmContext,
+    path: &Path,
+    sections_to_parse: &[usize],
+    checkpoints: Option<&SectionCheckpoints>,
+) -> Result<ProcessedFile, Box<dyn Error>> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let pdf_filename = stem.as_ref();
+
+    let t = Instant::now();
+    let pdf_text = extract_pdf_text(path).await?;
+    let pdf_extract = t.elapsed();
+
+    let parsed = parse_sections(
+        ctx,
+        &pdf_text,
+        sections_to_parse,
+        ctx.config.processing.section_concurrency,
+        checkpoints,
+    )
+    .await;
+    let mut timings = parsed.timings;
+    timings.pdf_extract = pdf_extract;
+
+    let mut pdf_data = serde_json::Map::new();
+    pdf_data.insert("filename".into(), Value::String(pdf_filename.into()));
+    pdf_data.extend(parsed.data);
+
+    if ctx.config.processing.debugging {
+        let t = Instant::now();
+        let markdown = build_markdown_for_pdf(pdf_filename, &pdf_text, sections_to_parse);
+        let md_path = format!("{}/{}.md", DEBUG_MARKDOWN_DIR, pdf_filename);
+        write_atomic(&md_path, markdown)?;
+
+        let trace = serde_json::json!({ "filename": pdf_filename, "sections": parsed.trace });
+        let trace_path = format!("{}/{}.trace.json", DEBUG_MARKDOWN_DIR, pdf_filename);
+        write_atomic(&trace_path, serde_json::to_string_pretty(&trace)?)?;
+        timings.markdown_write = t.elapsed();
+    }
+
+    Ok(ProcessedFile {
+        data: pdf_data,
+        usage: parsed.usage,
+        failed: parsed.failed,
+        timings,
+        review: parsed.review,
+        fingerprints: parsed.fingerprints,
+    })
+}
+
+/// Process one PDF of a batch and write its JSON (and markdown if debugging)
+///
+/// The file is skipped if the manifest shows its output is up to date and
+/// no earlier run was interrupted while working on it, unless
+/// `options.force` is set. Its start and end are recorded in the journal,
+/// and its manifest entry is marked as processing before any work starts,
+/// then as done, partial (some sections failed) or failed. The JSON file is
+/// replaced in one step, so it is never left truncated.
+///
+/// Each section is checkpointed as soon as it is parsed, and only sections
+/// without an up-to-date checkpoint are parsed, so a file processed again
+/// after an interruption or a failed section redoes only what is missing.
+/// Forcing a file discards its checkpoints.
+///
+/// Whatever step fails, the file is recorded as failed in the manifest and
+/// the journal before the error is returned, so the next run processes it
+/// again.
+///
+/// # Returns
+/// * `None` if the file was skipped, otherwise its token usage and stage timings
+pub(crate) async fn process_batch_file(
+    ctx: &LlmContext,
+    manifest: &Manifest,
+    journal: &RunJournal,
+    path: &Path,
+    output_dir: &str,
+    options: &ProcessOptions,
+) -> Result<Option<(TokenUsage, StageTimings)>, Box<dyn Error>> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let pdf_filename = stem.as_ref();
+    let result = try_process_batch_file(
+        ctx,
+        manifest,
+        journal,
+        path,
+        pdf_filename,
+        output_dir,
+        options,
+    )
+    .await;
+
+    if let Err(e) = &result {
+        let pdf_bytes = std::fs::read(path).unwrap_or_default();
+        let failed = Fingerprint::new(&pdf_bytes, BTreeMap::new());
+        let recorded = manifest
+            .record(
+                pdf_filename,
+                FileStatus::Failed,
+                &failed,
+                Some(e.to_string()),
+            )
+            .and_then(|()| journal.end(pdf_filename, FileStatus::Failed));
+        if let Err(record_error) = recorded {
+            tracing::warn!(
+                "Could not record the failure of {}: {}",
+                pdf_filename,
+                record_error
+            );
         }
+    }
+    result
+}
+
+/// [`process_batch_file`] up to the first error
+async fn try_process_batch_file(
+    ctx: &LlmContext,
+    manifest: &Manifest,
+    journal: &RunJournal,
+    path: &Path,
+    pdf_filename: &str,
+    output_dir: &str,
+    options: &ProcessOptions,
+) -> Result<Option<(TokenUsage, StageTimings)>, Box<dyn Error>> {
+    let file_timer = if ctx.config.processing.debugging {
+        Some(Timer::new("TOTAL FILE"))
+    } else {
+        None
+    };
+
+    let json_path = format!("{}/{}.json", output_dir, pdf_filename);
+
+    let sections_to_parse = &options.sections;
+    let pdf_bytes = std::fs::read(path)?;
+    let expected = ExpectedFingerprint::new(ctx, &pdf_bytes, sections_to_parse);
+    // Recorded until the sections are done, which are then recorded with it
+    let started = Fingerprint::new(&pdf_bytes, BTreeMap::new());
+    let reason = if options.force {
+        Some("forced".to_string())
+    } else if journal.interrupted(pdf_filename) {
+        Some("interrupted by an earlier run".to_string())
+    } else {
+        manifest
+            .reprocess_reason(pdf_filename, &expected)
+            .or_else(|| {
+                (!Path::new(&json_path).exists()).then(|| "output file is missing".to_string())
+            })
+    };
+    let Some(reason) = reason else {
+        tracing::info!("Skipping {} (up to date)", pdf_filename);
+        return Ok(None);
+    };
+
+    tracing::info!("Processing {} ({})", pdf_filename, reason);
+    journal.start(pdf_filename)?;
+    manifest.record(pdf_filename, FileStatus::Processing, &started, None)?;
+
+    let checkpoints = SectionCheckpoints::new(output_dir, pdf_filename, &expected);
+    if options.force {
+        checkpoints.clear();
+    }
+    let processed = process_file(ctx, path, sections_to_parse, Some(&checkpoints)).await?;
+    let mut timings = processed.timings;
+
+    let t = Instant::now();
+    write_atomic(&json_path, serde_json::to_string_pretty(&processed.data)?)?;
+    append_to_review_queue(output_dir, pdf_filename, processed.review)?;
+    let (status, error) = if processed.failed.is_empty() {
+        (FileStatus::Done, None)
+    } else {
+        let error = format!("{} failed", processed.failed.join(", "));
+        tracing::warn!("  {}: {}", pdf_filename, error);
+        (FileStatus::Partial, Some(error))
+    };
+    let fingerprint = Fingerprint::new(&pdf_bytes, processed.fingerprints);
+    manifest.record(pdf_filename, status, &fingerprint, error)?;
+    journal.end(pdf_filename, status)?;
+    timings.json_write = t.elapsed();
+
+    if let Some(timer) = file_timer {
+        timer.stop();
+    }
+
+    Ok(Some((processed.usage, timings)))
+}
+
+/// Process all PDFs in a directory and save parsed sections to JSON files
+///
+/// Up to `PDF_CONCURRENCY` files are processed at the same time, each with up
+/// to `SECTION_CONCURRENCY` sections in flight. Files are visited in name
+/// order and progress is reported in that order regardless of which file
+/// finishes first. The LLM backend is checked before the first file, and the
+/// run fails if it is unreachable or lacks the configured model. A file
+/// that fails is logged and recorded as failed, and the other files go on.
+///
+/// # Arguments
+/// * `ctx` - Configuration and providers of the run
+/// * `input_dir` - Directory containing PDF files to process
+/// * `output_dir` - Directory where JSON output files will be saved
+/// * `options` - Files and sections to process
+///
+/// # Returns
+/// * `Result<(), Box<dyn Error>>` - Success or error
+pub async fn process_pdfs_in_directory(
+    ctx: &LlmContext,
+    input_dir: &str,
+    output_dir: &str,
+    options: &ProcessOptions,
+) -> Result<(), Box<dyn Error>> {
+    let debugging = ctx.config.processing.debugging;
+    let started = SystemTime::now();
+
+    std::fs::create_dir_all(output_dir)?;
+    if debugging {
+        std::fs::create_dir_all(DEBUG_MARKDOWN_DIR)?;
+    }
+
+    let entries = list_pdfs(input_dir, &options.glob, options.recursive)?;
+    let manifest = Manifest::open(output_dir)?;
+    let journal = RunJournal::open(output_dir)?;
+
+    // Fail the run up front rather than every section of every file
+    if !entries.is_empty() {
+        preflight(ctx).await?;
+    }
+
+    let total = entries.len();
+    let start = Instant::now();
+    let mut completed = 0usize;
+
+    // Aggregate timers (only reported if debugging)
+    let mut totals = StageTimings::default();
+    let mut run_usage = TokenUsage::default();
+
+    // `buffered` keeps at most `file_concurrency` files in flight and yields
+    // their results in input order
+    let mut results = stream::iter(entries.iter())
+        .map(|path| process_batch_file(ctx, &manifest, &journal, path, output_dir, options))
+        .buffered(ctx.config.processing.file_concurrency);
+
+    let mut paths = entries.iter();
+    let mut failed = 0usize;
+    while let Some(result) = results.next().await {
+        let path = paths.next().unwrap();
+        let pdf_filename = path.file_stem().unwrap_or_default().to_string_lossy();
 
         completed += 1;
 
+        // Recorded as failed; the other files go on
+        let (usage, timings) = match result {
+            Ok(Some(done)) => done,
+            Ok(None) => continue,
+            Err(e) => {
+                failed += 1;
+                tracing::error!(
+                    "[{}/{}] Failed to process {}: {}",
+                    completed,
+                    total,
+                    path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        totals += timings;
+        run_usage += usage;
+
         let elapsed = start.elapsed();
         let avg = elapsed / completed as u32;
         let eta = avg * (total - completed) as u32;
 
         tracing::info!(
-            "[{}/{}] Finished {} | elapsed={} | ETA={}",
+            "[{}/{}] Finished {} | tokens={} | cost=${:.4} | elapsed={} | ETA={}",
             completed,
             total,
             pdf_filename,
+            usage.total_tokens(),
+            usage.estimated_cost,
             format_duration(elapsed),
             format_duration(eta)
         );
+    }
+
+    // Once the run is over, so that no write a concurrent run started just
+    // before this one can still be in flight
+    remove_stale_temp_files(output_dir, started)?;
+    journal.finish()?;
 
-        if let Some(timer) = file_timer {
-            timer.stop();
+    tracing::info!("============== USAGE SUMMARY ==============");
+    tracing::info!("Prompt tokens: {}", run_usage.prompt_tokens);
+    tracing::info!("Completion tokens: {}", run_usage.completion_tokens);
+    tracing::info!("Total tokens: {}", run_usage.total_tokens());
+    tracing::info!("Estimated cost: ${:.4}", run_usage.estimated_cost);
+    if failed > 0 {
+        tracing::info!("Failed files: {}", failed);
+    }
+    tracing::info!("==========================================");
+
+    let providers = ctx.router.stats();
+    if providers.len() > 1 {
+        tracing::info!("============ PROVIDER SUMMARY ============");
+        for provider in providers {
+            tracing::info!(
+                "{}: {} requests, {} failed{}",
+                provider.label,
+                provider.requests,
+                provider.failures,
+                if provider.available {
+                    ""
+                } else {
+                    " (out of rotation)"
+                }
+            );
         }
+        tracing::info!("==========================================");
     }
 
     // Summary report (only if debugging)
     if debugging {
         tracing::info!("========== PERFORMANCE SUMMARY ==========");
-        tracing::info!("PDF extraction total: {:?}", time_pdf_extract);
-        tracing::info!("Section extraction total: {:?}", time_section_extract);
-        tracing::info!("LLM parse total: {:?}", time_llm_parse);
-        tracing::info!("JSON write total: {:?}", time_json_write);
-        tracing::info!("Markdown write total: {:?}", time_markdown_write);
+        tracing::info!("PDF extraction total: {:?}", totals.pdf_extract);
+        tracing::info!("Section extraction total: {:?}", totals.section_extract);
+        tracing::info!("LLM parse total: {:?}", totals.llm_parse);
+        tracing::info!("JSON write total: {:?}", totals.json_write);
+        tracing::info!("Markdown write total: {:?}", totals.markdown_write);
         tracing::info!("==========================================");
     }
 
@@ -255,53 +936,126 @@ pub async fn process_pdfs_in_directory(
 
 /// Process a single PDF file and return the parsed data
 ///
+/// The file goes through the same steps as in [`process_pdfs_in_directory`]:
+/// the LLM backend is checked first, and the markdown and trace are written
+/// to `output_markdown/` if debugging. Low-confidence records are logged
+/// instead of being queued for review.
+///
 /// # Arguments
+/// * `ctx` - Configuration and providers to parse with
 /// * `pdf_path` - Path to the PDF file
-/// * `sections_to_parse` - Optional list of section indices to parse. If None, parses default sections.
+/// * `sections_to_parse` - Optional list of section indices to parse. If None, parses [`DEFAULT_SECTIONS`].
 ///
 /// # Returns
 /// * `Result<serde_json::Map<String, Value>, Box<dyn Error>>` - Parsed data or error
 pub async fn process_single_pdf(
+    ctx: &LlmContext,
     pdf_path: &str,
     sections_to_parse: Option<&[usize]>,
 ) -> Result<serde_json::Map<String, Value>, Box<dyn Error>> {
-    let client = Client::new();
-    let sections = sections_to_parse.unwrap_or(&[0, 1, 4, 5]);
+    let sections = sections_to_parse.unwrap_or(&DEFAULT_SECTIONS);
+
+    if ctx.config.processing.debugging {
+        std::fs::create_dir_all(DEBUG_MARKDOWN_DIR)?;
+    }
+    preflight(ctx).await?;
 
-    let pdf_filename = Path::new(pdf_path)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("unknown");
+    tracing::info!("Processing {}", pdf_path);
+    let processed = process_file(ctx, Path::new(pdf_path), sections, None).await?;
+    for record in &processed.review {
+        tracing::warn!(
+            "  Low-confidence record in {} at {}",
+            record["section"].as_str().unwrap_or_default(),
+            record["pointer"].as_str().unwrap_or_default()
+        );
+    }
+    tracing::info!(
+        "Finished {} | tokens={} | cost=${:.4}",
+        pdf_path,
+        processed.usage.total_tokens(),
+        processed.usage.estimated_cost
+    );
 
+    Ok(processed.data)
+}
+
+/// Add the sections of a corrected output file to the few-shot example bank
+///
+/// # Arguments
+/// * `ctx` - Run whose example bank (`FEW_SHOT_DIR`) receives the examples
+/// * `pdf_path` - PDF the output was produced from
+/// * `corrected` - Hand-corrected contents of its output JSON file
+/// * `sections` - Sections to add, or `None` for every section in the file
+///
+/// # Returns
+/// * Number of examples added
+pub fn add_examples_from_output(
+    ctx: &LlmContext,
+    pdf_path: &str,
+    corrected: &serde_json::Map<String, Value>,
+    sections: Option<&[usize]>,
+) -> Result<usize, Box<dyn Error>> {
     let pdf_text = get_text_from_pdf(pdf_path);
+    let mut added = 0;
 
-    let mut pdf_data = serde_json::Map::new();
-    pdf_data.insert(
-        "filename".to_string(),
-        Value::String(pdf_filename.to_string()),
-    );
+    for section_index in 0..crate::ALL_SECTIONS.len() {
+        if sections.is_some_and(|sections| !sections.contains(&section_index)) {
+            continue;
+        }
+        let (Some(key), Some(parser)) = (
+            output_key(section_index),
+            SectionParser::from_section_index(section_index),
+        ) else {
+            continue;
+        };
+        let Some(value) = corrected.get(key) else {
+            continue;
+        };
 
-    for &section_index in sections {
         let section_text = extract_section(section_index, &pdf_text);
-        let section_name = SectionParser::section_name(section_index);
-
         if section_text.trim().is_empty() {
             continue;
         }
 
-        if let Some(parser) = SectionParser::from_section_index(section_index) {
-            match parser.parse(&client, &section_text, section_name).await {
-                Ok(json) => {
-                    if let Some((key, value)) = output_key_and_value(section_index, json) {
-                        pdf_data.insert(key, value);
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!("Warning: {} parse error: {}", section_name, e);
+        parser.add_example(ctx, &section_text, value.clone())?;
+        added += 1;
+    }
+
+    Ok(added)
+}
+
+/// Check the sections of an output file against their models
+///
+/// # Arguments
+/// * `data` - Contents of an output JSON file
+///
+/// # Returns
+/// * One message per section that does not match its model or has fields
+///   that fail validation; empty if the file is valid
+pub fn validate_output(data: &serde_json::Map<String, Value>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for section_index in 0..crate::ALL_SECTIONS.len() {
+        let (Some(key), Some(parser)) = (
+            output_key(section_index),
+            SectionParser::from_section_index(section_index),
+        ) else {
+            continue;
+        };
+        let Some(value) = data.get(key) else {
+            continue;
+        };
+
+        match parser.coerce(value.clone()) {
+            Ok(value) => {
+                let invalid = invalid_fields(&value);
+                if !invalid.is_empty() {
+                    problems.push(format!("{}: invalid fields {}", key, invalid.join(", ")));
                 }
             }
+            Err(e) => problems.push(format!("{}: {}", key, e)),
         }
     }
 
-    Ok(pdf_data)
+    problems
 }
@@ -0,0 +1,472 @@
+//! Two-step processing through a provider's batch endpoint: requests are
+//! exported to a JSONL file for upload, and the results file returned by the
+//! provider is ingested later into the usual per-company JSON files.
+//!
+//! Ingested sections are scored and grounded against the text of their PDF
+//! like synchronously parsed ones, so the output files have the same keys,
+//! and ingested files are recorded in the processing manifest, so a later
+//! run does not extract them again.
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::pricing::PriceTable;
+use crate::models::api::TokenUsage;
+use crate::parser::confidence::ExtractionStrategy;
+use crate::parser::context::LlmContext;
+use crate::parser::ollama::{ParsedSection, SectionParser};
+use crate::parser::section::extract_section;
+use crate::processor::atomic::write_atomic;
+use crate::processor::batch::{
+    append_to_review_queue, assemble_sections, extract_pdf_text, list_pdfs, SectionOutcome,
+    DEFAULT_SECTIONS,
+};
+use crate::processor::checkpoint::SectionCheckpoints;
+use crate::processor::manifest::{ExpectedFingerprint, FileStatus, Fingerprint, Manifest};
+
+/// Endpoint every exported request is addressed to
+const BATCH_URL: &str = "/v1/responses";
+
+/// Stable id of a section request, e.g. `acme::4`
+pub fn custom_id(pdf_filename: &str, section_index: usize) -> String {
+    format!("{}::{}", pdf_filename, section_index)
+}
+
+/// Split a custom id back into the file name and section index
+///
+/// The file name comes from an untrusted results file and becomes an output
+/// path, so names that are not plain file names are rejected.
+pub fn parse_custom_id(id: &str) -> Option<(&str, usize)> {
+    let (pdf_filename, section_index) = id.rsplit_once("::")?;
+    if !is_plain_file_name(pdf_filename) {
+        return None;
+    }
+    Some((pdf_filename, section_index.parse().ok()?))
+}
+
+/// Whether a name stays in the directory it is joined to: not empty, `.` or
+/// `..`, and without path separators or NUL
+///
+/// Export and ingest apply the same rule, so every exported file can be
+/// ingested.
+fn is_plain_file_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains(['/', '\\', '\0'])
+}
+
+/// Write one batch request line per non-empty section of every PDF in a directory
+///
+/// # Arguments
+/// * `ctx` - Run choosing the model and prompt of each section
+/// * `input_dir` - Directory containing PDF files
+/// * `requests_path` - JSONL file to write, in the OpenAI batch input format
+///
+/// # Returns
+/// * Number of requests written
+pub async fn export_batch_requests(
+    ctx: &LlmContext,
+    input_dir: &str,
+    requests_path: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let mut lines = String::new();
+    let mut count = 0;
+
+    for path in list_pdfs(input_dir, "*.pdf", false)? {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let pdf_filename = stem.as_ref();
+        if !is_plain_file_name(pdf_filename) {
+            tracing::warn!("{} skipped: its name cannot be ingested", path.display());
+            continue;
+        }
+        let pdf_text = extract_pdf_text(&path).await?;
+
+        for section_index in DEFAULT_SECTIONS {
+            let section_name = SectionParser::section_name(section_index);
+            let section_text = extract_section(section_index, &pdf_text);
+            let Some(parser) = SectionParser::from_section_index(section_index) else {
+                continue;
+            };
+            if section_text.trim().is_empty() {
+                continue;
+            }
+
+            let line = json!({
+                "custom_id": custom_id(pdf_filename, section_index),
+                "method": "POST",
+                "url": BATCH_URL,
+                "body": parser.batch_request(ctx, &section_text, section_name)?,
+            });
+            lines.push_str(&serde_json::to_string(&line)?);
+            lines.push('\n');
+            count += 1;
+        }
+    }
+
+    std::fs::write(requests_path, lines)?;
+    tracing::info!("Wrote {} batch requests to {}", count, requests_path);
+    Ok(count)
+}
+
+/// Structured reply and token usage of a Responses API body. The parsed
+/// output is taken from `output_parsed` or else from the first output text.
+fn parse_reply(body: &Value, prices: &PriceTable) -> Result<(Value, TokenUsage), Box<dyn Error>> {
+    let value = match body.get("output_parsed") {
+        Some(parsed) if !parsed.is_null() => parsed.clone(),
+        _ => {
+            let text = body["output"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(|item| item["content"].as_array().into_iter().flatten())
+                .find_map(|content| content["text"].as_str())
+                .ok_or("reply has no output text")?;
+            serde_json::from_str(text)?
+        }
+    };
+
+    let prompt_tokens = body["usage"]["input_tokens"].as_u64().unwrap_or(0);
+    let completion_tokens = body["usage"]["output_tokens"].as_u64().unwrap_or(0);
+    let model = body["model"].as_str().unwrap_or_default();
+    let usage = TokenUsage {
+        prompt_tokens,
+        completion_tokens,
+        estimated_cost: prices.cost(model, prompt_tokens, completion_tokens),
+    };
+    Ok((value, usage))
+}
+
+/// Read a batch results file and write the JSON file of every company in it
+///
+/// Each line is deserialized into its section's model by [`SectionParser`],
+/// then scored and grounded against the section text of the PDF it was
+/// exported from. Sections whose line failed, does not match the model or is
+/// missing are listed under `errors`, as failed sections of `process` are.
+/// Lines that are not JSON or whose custom ID is not a plain file name are
+/// logged with their line number and skipped, and their count is logged as an
+/// error at the end; files whose PDF is no longer in `input_dir` are skipped
+/// too. Each file written is
+/// recorded in the manifest with the model and prompt version its sections
+/// were requested with, and its sections are checkpointed, so a later run
+/// only sends the failed ones again.
+///
+/// # Arguments
+/// * `ctx` - Run whose price table the cost is estimated with and whose
+///   configuration the ingested sections are checked against
+/// * `input_dir` - Directory the requests were exported from
+/// * `results_path` - JSONL results file in the OpenAI batch output format
+/// * `output_dir` - Directory where JSON output files will be saved
+///
+/// # Returns
+/// * Number of JSON files written
+pub async fn ingest_batch_results(
+    ctx: &LlmContext,
+    input_dir: &str,
+    results_path: &str,
+    output_dir: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let config = &ctx.config;
+    let reader = BufReader::new(std::fs::File::open(results_path)?);
+    // File name → section index → extracted section or why it failed
+    type Results = BTreeMap<usize, Result<ParsedSection, String>>;
+    let mut files: BTreeMap<String, Results> = BTreeMap::new();
+    let mut unreadable = 0;
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result: Value = match serde_json::from_str(&line) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Batch result line {} skipped: {}", number + 1, e);
+                unreadable += 1;
+                continue;
+            }
+        };
+        let id = result["custom_id"].as_str().unwrap_or_default();
+
+        let Some((pdf_filename, section_index)) = parse_custom_id(id) else {
+            tracing::warn!(
+                "Batch result line {} skipped: invalid custom_id {:?}",
+                number + 1,
+                id
+            );
+            unreadable += 1;
+            continue;
+        };
+        let parsed = ingest_line(&result, section_index, &config.prices).map_err(|e| {
+            tracing::warn!("Batch result line {} ({}) failed: {}", number + 1, id, e);
+            e.to_string()
+        });
+        files
+            .entry(pdf_filename.to_string())
+            .or_default()
+            .insert(section_index, parsed);
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let manifest = Manifest::open(output_dir)?;
+    let mut written = 0;
+    for (pdf_filename, sections) in files {
+        let pdf_path = Path::new(input_dir).join(format!("{}.pdf", pdf_filename));
+        if !pdf_path.is_file() {
+            tracing::warn!(
+                "Batch results of {} skipped: {} not found",
+                pdf_filename,
+                pdf_path.display()
+            );
+            continue;
+        }
+        let pdf_bytes = std::fs::read(&pdf_path)?;
+        let pdf_text = extract_pdf_text(&pdf_path).await?;
+        let expected = ExpectedFingerprint::new(ctx, &pdf_bytes, &DEFAULT_SECTIONS);
+        let checkpoints = SectionCheckpoints::new(output_dir, &pdf_filename, &expected);
+
+        tracing::info!("Ingesting {}", pdf_filename);
+        let mut sections = sections;
+        let outcomes = DEFAULT_SECTIONS
+            .into_iter()
+            .filter_map(|section_index| {
+                let parser = SectionParser::from_section_index(section_index)?;
+                let section_name = SectionParser::section_name(section_index);
+                let section_text = extract_section(section_index, &pdf_text);
+                let result = match sections.remove(&section_index) {
+                    Some(Ok(mut parsed)) => {
+                        parser.score(&mut parsed, &section_text, section_name, &config.processing);
+                        if let Err(e) = checkpoints.save(section_index, &parsed) {
+                            tracing::warn!("  Could not save {} checkpoint: {}", section_name, e);
+                        }
+                        Some(Ok(parsed))
+                    }
+                    Some(Err(e)) => {
+                        checkpoints.remove(section_index);
+                        Some(Err(e))
+                    }
+                    // Empty sections were not exported; recorded as such
+                    None if section_text.trim().is_empty() => None,
+                    None => Some(Err(format!("no result in {}", results_path))),
+                };
+                Some(SectionOutcome {
+                    section_index,
+                    section_name,
+                    result,
+                    reused: false,
+                    trace: Vec::new(),
+                    latency: Duration::ZERO,
+                })
+            })
+            .collect();
+        let assembled = assemble_sections(config, &DEFAULT_SECTIONS, outcomes);
+
+        let mut pdf_data = Map::new();
+        pdf_data.insert("filename".into(), Value::String(pdf_filename.clone()));
+        pdf_data.extend(assembled.data);
+        let json_path = format!("{}/{}.json", output_dir, pdf_filename);
+        write_atomic(&json_path, serde_json::to_string_pretty(&pdf_data)?)?;
+        append_to_review_queue(output_dir, &pdf_filename, assembled.review)?;
+        let (status, error) = if assembled.failed.is_empty() {
+            (FileStatus::Done, None)
+        } else {
+            let error = format!("{} failed", assembled.failed.join(", "));
+            tracing::warn!("  {}: {}", pdf_filename, error);
+            (FileStatus::Partial, Some(error))
+        };
+        let fingerprint = Fingerprint::new(&pdf_bytes, assembled.fingerprints);
+        manifest.record(&pdf_filename, status, &fingerprint, error)?;
+        written += 1;
+    }
+
+    tracing::info!("Ingested {} into {} JSON files", results_path, written);
+    if unreadable > 0 {
+        tracing::error!(
+            "{} lines of {} could not be ingested, see the warnings above",
+            unreadable,
+            results_path
+        );
+    }
+    Ok(written)
+}
+
+/// Check the status of one result line and coerce its reply into the section's model
+fn ingest_line(
+    result: &Value,
+    section_index: usize,
+    prices: &PriceTable,
+) -> Result<ParsedSection, Box<dyn Error>> {
+    if !result["error"].is_null() {
+        return Err(format!("request failed: {}", result["error"]).into());
+    }
+    let response = &result["response"];
+    let status = response["status_code"].as_u64().unwrap_or(0);
+    if status != 200 {
+        return Err(format!("HTTP status {}", status).into());
+    }
+
+    let parser = SectionParser::from_section_index(section_index)
+        .ok_or_else(|| format!("no parser for section {}", section_index))?;
+    let (value, usage) = parse_reply(&response["body"], prices)?;
+    let metadata = &response["body"]["metadata"];
+    let prompt_version = metadata["promptVersion"].as_str().unwrap_or("unknown");
+    // The model as requested, as for synchronous requests, rather than the
+    // dated snapshot the reply names
+    let model = match metadata["model"].as_str() {
+        Some(model) => model.to_string(),
+        None => format!(
+            "openai={}",
+            response["body"]["model"].as_str().unwrap_or("unknown")
+        ),
+    };
+    Ok(ParsedSection {
+        value: parser.coerce(value)?,
+        usage,
+        prompt_version: prompt_version.to_string(),
+        ungrounded: Vec::new(),
+        strategy: ExtractionStrategy::Single,
+        confidence: BTreeMap::new(),
+        escalated_to: None,
+        model,
+        options: Value::Null,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Settings};
+    use crate::mock::minimal_pdf;
+
+    fn config() -> Config {
+        Config::from_settings(&Settings::new()).unwrap()
+    }
+
+    fn context() -> LlmContext {
+        LlmContext::new(config()).unwrap()
+    }
+
+    fn result_line(id: &str, body: Value) -> String {
+        json!({
+            "id": "batch_req_1",
+            "custom_id": id,
+            "response": { "status_code": 200, "body": body },
+            "error": null
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_custom_id_round_trip() {
+        assert_eq!(
+            parse_custom_id(&custom_id("acme::ltd", 4)),
+            Some(("acme::ltd", 4))
+        );
+        assert_eq!(parse_custom_id("acme"), None);
+        assert_eq!(parse_custom_id("acme::x"), None);
+        assert_eq!(parse_custom_id("../../x::4"), None);
+        assert_eq!(parse_custom_id("a/b::4"), None);
+        assert_eq!(parse_custom_id("a\\b::4"), None);
+        assert_eq!(parse_custom_id("::4"), None);
+        assert_eq!(parse_custom_id("..::4"), None);
+        assert_eq!(parse_custom_id(".::4"), None);
+        assert_eq!(parse_custom_id("A..B::4"), Some(("A..B", 4)));
+    }
+
+    #[test]
+    fn test_parse_reply_output_text() {
+        let body = json!({
+            "model": "gpt-4.1-mini",
+            "output": [{ "type": "message", "content": [
+                { "type": "output_text", "text": "{\"officeBearers\":[]}" }
+            ]}],
+            "usage": { "input_tokens": 1000, "output_tokens": 10 }
+        });
+        let (value, usage) = parse_reply(&body, &config().prices).unwrap();
+        assert_eq!(value, json!({ "officeBearers": [] }));
+        assert_eq!(usage.prompt_tokens, 1000);
+        assert_eq!(usage.completion_tokens, 10);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_batch_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_dir = dir.path().join("pdf");
+        std::fs::create_dir_all(&input_dir).unwrap();
+        std::fs::write(
+            input_dir.join("acme.pdf"),
+            minimal_pdf(&[
+                "Company Details",
+                "Business Details",
+                "Office Bearers",
+                "DIRECTOR DOE JOHN 01/02/2003",
+            ]),
+        )
+        .unwrap();
+        let results = dir.path().join("results.jsonl");
+        let bearers = json!({ "output_parsed": { "officeBearers": [{
+            "position": "DIRECTOR", "name": "DOE JOHN", "address": "",
+            "country": "", "appointedDate": "01/02/2003", "entityType": ""
+        }]}});
+        let lines = [
+            result_line("acme::4", bearers),
+            result_line("acme::1", json!({ "output_parsed": [] })),
+            result_line("acme::0", json!({ "output_parsed": { "wrong": true } })),
+            json!({ "custom_id": "beta::4", "response": null, "error": { "code": "x" } })
+                .to_string(),
+            result_line(
+                "../escaped::4",
+                json!({ "output_parsed": { "officeBearers": [] } }),
+            ),
+            // No PDF to score it against
+            result_line(
+                "gamma::4",
+                json!({ "output_parsed": { "officeBearers": [] } }),
+            ),
+        ];
+        std::fs::write(&results, lines.join("\n")).unwrap();
+
+        let output_dir = dir.path().join("out");
+        let written = ingest_batch_results(
+            &context(),
+            input_dir.to_str().unwrap(),
+            results.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, 1);
+        assert!(!dir.path().join("escaped.json").exists());
+
+        let output: Value =
+            serde_json::from_slice(&std::fs::read(output_dir.join("acme.json")).unwrap()).unwrap();
+        assert_eq!(output["filename"], "acme");
+        assert_eq!(output["officeBearers"][0]["name"], "DOE JOHN");
+        assert_eq!(output["businessDetails"], json!([]));
+        assert!(output.get("companyDetails").is_none());
+        // The reply did not match the model
+        assert!(output["errors"]["Company Details"].is_string());
+        assert!(output["tokenUsage"]["sections"]["Office Bearers"].is_object());
+        assert_eq!(output["promptVersions"]["Office Bearers"], "unknown");
+        assert!(output["confidence"]["Office Bearers"].is_object());
+        // The address was left empty, the name is in the section
+        assert!(output.get("ungroundedFields").is_none());
+
+        let manifest = Manifest::open(output_dir.to_str().unwrap()).unwrap();
+        let entry = manifest.get("acme").unwrap();
+        assert_eq!(entry.status, FileStatus::Partial);
+        assert!(!entry.fingerprint.sections.contains_key("Company Details"));
+        let bearers = entry.fingerprint.sections["Office Bearers"]
+            .as_ref()
+            .unwrap();
+        assert_eq!(bearers.model, "openai=unknown");
+        // Checkpointed like a synchronously parsed section
+        let checkpoint = output_dir
+            .join(".state")
+            .join("sections")
+            .join("acme")
+            .join("4.json");
+        assert!(checkpoint.is_file());
+    }
+}
@@ -0,0 +1,187 @@
+//! Result of each section of a file, saved as soon as the section is parsed
+//! in `<output_dir>/.state/sections/<file>/<section index>.json`.
+//!
+//! When a file is processed again, a section is taken from its checkpoint if
+//! it was parsed from the same PDF with a prompt, schema, model and options
+//! the configuration still uses, and scored with the same grounding and
+//! confidence settings, so only missing, failed or out-of-date sections are
+//! sent to the LLM.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::models::api::TokenUsage;
+use crate::parser::confidence::ExtractionStrategy;
+use crate::parser::ollama::{ParsedSection, SectionParser};
+use crate::processor::atomic::write_atomic;
+use crate::processor::manifest::{ExpectedFingerprint, SectionFingerprint, STATE_DIR};
+
+/// A parsed section and what it was parsed from
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SectionCheckpoint {
+    source_hash: String,
+    fingerprint: SectionFingerprint,
+    value: Value,
+    usage: TokenUsage,
+    ungrounded: Vec<String>,
+    confidence: BTreeMap<String, f64>,
+    escalated_to: Option<String>,
+}
+
+/// Section checkpoints of one file
+pub struct SectionCheckpoints {
+    dir: PathBuf,
+    expected: ExpectedFingerprint,
+}
+
+impl SectionCheckpoints {
+    /// # Arguments
+    /// * `output_dir` - Directory of the output files
+    /// * `pdf_filename` - File stem of the PDF
+    /// * `expected` - What the file may be processed with in this run
+    pub fn new(output_dir: &str, pdf_filename: &str, expected: &ExpectedFingerprint) -> Self {
+        Self {
+            dir: Path::new(output_dir)
+                .join(STATE_DIR)
+                .join("sections")
+                .join(pdf_filename),
+            expected: expected.clone(),
+        }
+    }
+
+    fn path(&self, section_index: usize) -> PathBuf {
+        self.dir.join(format!("{}.json", section_index))
+    }
+
+    /// The saved result of a section, if it is still up to date
+    pub fn load(&self, section_index: usize) -> Option<ParsedSection> {
+        let bytes = std::fs::read(self.path(section_index)).ok()?;
+        let checkpoint: SectionCheckpoint = serde_json::from_slice(&bytes).ok()?;
+        let name = SectionParser::section_name(section_index);
+        let up_to_date = checkpoint.source_hash == self.expected.source_hash
+            && self
+                .expected
+                .sections
+                .get(name)
+                .is_some_and(|e| e.difference(name, &checkpoint.fingerprint).is_none());
+        if !up_to_date {
+            return None;
+        }
+
+        Some(ParsedSection {
+            value: checkpoint.value,
+            usage: checkpoint.usage,
+            prompt_version: checkpoint.fingerprint.prompt_version,
+            ungrounded: checkpoint.ungrounded,
+            // Only used to score confidence, which is saved already scored
+            strategy: ExtractionStrategy::Single,
+            confidence: checkpoint.confidence,
+            escalated_to: checkpoint.escalated_to,
+            model: checkpoint.fingerprint.model,
+            options: checkpoint.fingerprint.options,
+        })
+    }
+
+    /// Save the result of a section
+    pub fn save(&self, section_index: usize, parsed: &ParsedSection) -> Result<(), Box<dyn Error>> {
+        let parser = SectionParser::from_section_index(section_index)
+            .ok_or_else(|| format!("section {} has no parser", section_index))?;
+        let checkpoint = SectionCheckpoint {
+            source_hash: self.expected.source_hash.clone(),
+            fingerprint: SectionFingerprint::of(&parser, parsed, &self.expected.scoring),
+            value: parsed.value.clone(),
+            usage: parsed.usage,
+            ungrounded: parsed.ungrounded.clone(),
+            confidence: parsed.confidence.clone(),
+            escalated_to: parsed.escalated_to.clone(),
+        };
+
+        std::fs::create_dir_all(&self.dir)?;
+        write_atomic(self.path(section_index), serde_json::to_vec(&checkpoint)?)?;
+        Ok(())
+    }
+
+    /// Forget every section of the file
+    pub fn clear(&self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+
+    /// Forget a section, e.g. after it failed
+    pub fn remove(&self, section_index: usize) {
+        let _ = std::fs::remove_file(self.path(section_index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Settings};
+    use crate::models::api::LlmBackend;
+    use crate::parser::context::LlmContext;
+    use crate::parser::ollama::request_options;
+    use serde_json::json;
+
+    fn context(pairs: &[(&str, &str)]) -> LlmContext {
+        let settings: Settings = pairs.iter().copied().collect();
+        LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap()
+    }
+
+    fn expected(ctx: &LlmContext, pdf: &[u8]) -> ExpectedFingerprint {
+        ExpectedFingerprint::new(ctx, pdf, &[0, 4])
+    }
+
+    #[test]
+    fn test_checkpoints_are_reused_while_up_to_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap();
+        let ctx = context(&[]);
+        let parsed = ParsedSection {
+            value: json!({ "officeBearers": [] }),
+            usage: TokenUsage {
+                prompt_tokens: 100,
+                completion_tokens: 10,
+                estimated_cost: 0.0,
+            },
+            prompt_version: SectionParser::OfficeBearers.prompt_version(&ctx, LlmBackend::Ollama),
+            ungrounded: Vec::new(),
+            strategy: ExtractionStrategy::Single,
+            confidence: BTreeMap::new(),
+            escalated_to: None,
+            model: format!("ollama={}", ctx.config.llm.ollama_model),
+            options: request_options(&ctx.config.llm, LlmBackend::Ollama, Some(4096)),
+        };
+
+        let checkpoints = SectionCheckpoints::new(output_dir, "acme", &expected(&ctx, b"pdf"));
+        assert!(checkpoints.load(4).is_none());
+        checkpoints.save(4, &parsed).unwrap();
+        let loaded = checkpoints.load(4).unwrap();
+        assert_eq!(loaded.value, parsed.value);
+        assert_eq!(loaded.usage.prompt_tokens, 100);
+        assert_eq!(loaded.model, parsed.model);
+        assert_eq!(loaded.options, parsed.options);
+        assert!(checkpoints.load(0).is_none());
+
+        let replaced = SectionCheckpoints::new(output_dir, "acme", &expected(&ctx, b"new pdf"));
+        assert!(replaced.load(4).is_none());
+        let routed = context(&[("LLM_MODEL_OFFICE_BEARERS", "openai=gpt-4.1")]);
+        let rerouted = SectionCheckpoints::new(output_dir, "acme", &expected(&routed, b"pdf"));
+        assert!(rerouted.load(4).is_none());
+        let warmer = context(&[("OLLAMA_TEMPERATURE", "0.7")]);
+        let retuned = SectionCheckpoints::new(output_dir, "acme", &expected(&warmer, b"pdf"));
+        assert!(retuned.load(4).is_none());
+        // Saved already scored, so not reused once scoring changes
+        for setting in [("GROUNDING_POLICY", "clear"), ("MIN_CONFIDENCE", "0.5")] {
+            let rescored = context(&[setting]);
+            let rescored =
+                SectionCheckpoints::new(output_dir, "acme", &expected(&rescored, b"pdf"));
+            assert!(rescored.load(4).is_none(), "{:?}", setting);
+        }
+
+        checkpoints.remove(4);
+        assert!(checkpoints.load(4).is_none());
+    }
+}
@@ -0,0 +1,180 @@
+//! Append-only log of the files each run starts and finishes, kept in
+//! `<output_dir>/.state/journal.jsonl`.
+//!
+//! A file started in an earlier run but never finished was interrupted, e.g.
+//! by a crash or Ctrl-C, and is processed again whatever the manifest says.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::processor::manifest::{FileStatus, STATE_DIR};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum JournalEvent {
+    RunStart,
+    RunEnd,
+    Start,
+    End,
+}
+
+/// One line of the journal
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalLine {
+    run: String,
+    event: JournalEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<FileStatus>,
+    /// Seconds since the Unix epoch
+    at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Journal of the current run, shared by its files
+pub struct RunJournal {
+    run: String,
+    file: Mutex<File>,
+    /// Files started in an earlier run and never finished
+    interrupted: Mutex<HashSet<String>>,
+}
+
+impl RunJournal {
+    /// Read the journal of `output_dir` and record the start of a new run
+    ///
+    /// Lines that cannot be parsed, such as one cut short by a crash, are ignored.
+    pub fn open(output_dir: &str) -> Result<Self, Box<dyn Error>> {
+        let dir = Path::new(output_dir).join(STATE_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("journal.jsonl");
+
+        let mut interrupted = HashSet::new();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e).into()),
+        };
+        for line in text.lines() {
+            let Ok(line) = serde_json::from_str::<JournalLine>(line) else {
+                continue;
+            };
+            match (line.event, line.file) {
+                (JournalEvent::Start, Some(file)) => {
+                    interrupted.insert(file);
+                }
+                (JournalEvent::End, Some(file)) => {
+                    interrupted.remove(&file);
+                }
+                _ => {}
+            }
+        }
+        if !interrupted.is_empty() {
+            tracing::warn!(
+                "{} files were left unfinished by an earlier run and will be processed again",
+                interrupted.len()
+            );
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // Finish a line cut short, so that the next one can be read
+        if !text.is_empty() && !text.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+        let journal = Self {
+            run: format!("{}-{}", now(), std::process::id()),
+            file: Mutex::new(file),
+            interrupted: Mutex::new(interrupted),
+        };
+        journal.append(JournalEvent::RunStart, None, None)?;
+        Ok(journal)
+    }
+
+    fn append(
+        &self,
+        event: JournalEvent,
+        file: Option<&str>,
+        status: Option<FileStatus>,
+    ) -> Result<(), Box<dyn Error>> {
+        let line = JournalLine {
+            run: self.run.clone(),
+            event,
+            file: file.map(str::to_string),
+            status,
+            at: now(),
+        };
+        let mut text = serde_json::to_string(&line)?;
+        text.push('\n');
+        // One write per line, so that lines of concurrent files never interleave
+        let mut journal = self.file.lock().unwrap();
+        journal.write_all(text.as_bytes())?;
+        journal.sync_data()?;
+        Ok(())
+    }
+
+    /// Whether an earlier run started this file without finishing it
+    pub fn interrupted(&self, pdf_filename: &str) -> bool {
+        self.interrupted.lock().unwrap().contains(pdf_filename)
+    }
+
+    /// Record that work on a file starts
+    pub fn start(&self, pdf_filename: &str) -> Result<(), Box<dyn Error>> {
+        self.append(JournalEvent::Start, Some(pdf_filename), None)
+    }
+
+    /// Record that a file is finished, its output written or its failure recorded
+    pub fn end(&self, pdf_filename: &str, status: FileStatus) -> Result<(), Box<dyn Error>> {
+        self.append(JournalEvent::End, Some(pdf_filename), Some(status))?;
+        self.interrupted.lock().unwrap().remove(pdf_filename);
+        Ok(())
+    }
+
+    /// Record that the run ended normally
+    pub fn finish(&self) -> Result<(), Box<dyn Error>> {
+        self.append(JournalEvent::RunEnd, None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unfinished_files_are_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap();
+
+        // A run that is killed while working on beta
+        let journal = RunJournal::open(output_dir).unwrap();
+        journal.start("acme").unwrap();
+        journal.start("beta").unwrap();
+        journal.end("acme", FileStatus::Done).unwrap();
+        drop(journal);
+        let path = dir.path().join(STATE_DIR).join("journal.jsonl");
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"run\":\"1\",\"ev").unwrap();
+
+        let journal = RunJournal::open(output_dir).unwrap();
+        assert!(!journal.interrupted("acme"));
+        assert!(journal.interrupted("beta"));
+        journal.start("beta").unwrap();
+        journal.end("beta", FileStatus::Done).unwrap();
+        assert!(!journal.interrupted("beta"));
+        journal.finish().unwrap();
+
+        let journal = RunJournal::open(output_dir).unwrap();
+        assert!(!journal.interrupted("beta"));
+    }
+}
@@ -1 +1,7 @@
+pub mod atomic;
 pub mod batch;
+pub mod batch_api;
+pub mod checkpoint;
+pub mod journal;
+pub mod manifest;
+pub mod watch;
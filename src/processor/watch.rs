@@ -0,0 +1,338 @@
+//! Long-running processing of the PDFs that land in an input directory.
+//!
+//! Files are picked up once they have stopped changing for
+//! `WATCH_DEBOUNCE_MS`, so that a copy still in progress is not read half
+//! written, and go through the same steps as a batch run.
+
+use futures::stream::{self, StreamExt};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc;
+
+use crate::parser::context::LlmContext;
+use crate::parser::preflight::preflight;
+use crate::processor::batch::{
+    is_listed, list_pdfs, process_batch_file, remove_stale_temp_files, ProcessOptions,
+    DEBUG_MARKDOWN_DIR,
+};
+use crate::processor::journal::RunJournal;
+use crate::processor::manifest::Manifest;
+
+/// A file seen changing, waiting for its writes to settle
+struct Pending {
+    last_change: Instant,
+    /// Size at the last check, compared with the next one
+    len: Option<u64>,
+}
+
+/// Files waiting to be processed, keyed by path
+#[derive(Default)]
+struct PendingFiles {
+    files: HashMap<PathBuf, Pending>,
+}
+
+impl PendingFiles {
+    /// Record that a file was created or written
+    fn changed(&mut self, path: PathBuf, now: Instant) {
+        self.files.insert(
+            path,
+            Pending {
+                last_change: now,
+                len: None,
+            },
+        );
+    }
+
+    /// Take the files that have not changed for `debounce` and whose size
+    /// is the same as at the previous check. Files that no longer exist are
+    /// dropped.
+    fn settled(&mut self, debounce: Duration, now: Instant) -> Vec<PathBuf> {
+        let mut ready = Vec::new();
+        self.files.retain(|path, pending| {
+            if now.duration_since(pending.last_change) < debounce {
+                return true;
+            }
+            let Ok(metadata) = std::fs::metadata(path) else {
+                return false;
+            };
+            if pending.len == Some(metadata.len()) {
+                ready.push(path.clone());
+                return false;
+            }
+            pending.len = Some(metadata.len());
+            pending.last_change = now;
+            true
+        });
+        ready.sort();
+        ready
+    }
+}
+
+/// Record `path` as the file whose output is named after its stem.
+///
+/// # Returns
+/// * `false` if another existing file already writes that output, which is
+///   logged as an error; the file must then not be processed
+fn claim_output_name(names: &mut HashMap<OsString, PathBuf>, path: &Path) -> bool {
+    let stem = path.file_stem().unwrap_or_default().to_owned();
+    match names.get(&stem) {
+        Some(owner) if owner != path && owner.exists() => {
+            tracing::error!(
+                "Not processing {}: {} already writes {}.json; rename one of them",
+                path.display(),
+                owner.display(),
+                stem.to_string_lossy()
+            );
+            false
+        }
+        _ => {
+            names.insert(stem, path.to_path_buf());
+            true
+        }
+    }
+}
+
+/// Process a set of files, at most `PDF_CONCURRENCY` at a time. Each JSON
+/// file is written as soon as its PDF is done. A failed file is logged and
+/// does not stop the others. Once `stopping` is set, no further file is
+/// started, and the call returns when the files in progress are done.
+async fn process_files(
+    ctx: &LlmContext,
+    manifest: &Manifest,
+    journal: &RunJournal,
+    paths: Vec<PathBuf>,
+    output_dir: &str,
+    options: &ProcessOptions,
+    stopping: &AtomicBool,
+) {
+    let mut results = stream::iter(paths)
+        .take_while(|_| std::future::ready(!stopping.load(Ordering::SeqCst)))
+        .map(|path| async move {
+            let result =
+                process_batch_file(ctx, manifest, journal, &path, output_dir, options).await;
+            (path, result)
+        })
+        .buffer_unordered(ctx.config.processing.file_concurrency);
+
+    while let Some((path, result)) = results.next().await {
+        match result {
+            Ok(Some((usage, _))) => tracing::info!(
+                "Finished {} | tokens={} | cost=${:.4}",
+                path.display(),
+                usage.total_tokens(),
+                usage.estimated_cost
+            ),
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to process {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Wait for `files` to be processed, unless `shutdown` completes first: then
+/// `stopping` is set so that no further file is started, and the call
+/// returns once the files in progress are done.
+///
+/// # Returns
+/// * `true` if `shutdown` completed
+async fn process_until_shutdown(
+    files: impl Future<Output = ()>,
+    mut shutdown: Pin<&mut impl Future<Output = ()>>,
+    stopping: &AtomicBool,
+) -> bool {
+    tokio::pin!(files);
+    tokio::select! {
+        _ = &mut files => false,
+        _ = &mut shutdown => {
+            stopping.store(true, Ordering::SeqCst);
+            files.await;
+            true
+        }
+    }
+}
+
+/// Process the PDFs of a directory, then every PDF created or changed in it
+/// until `shutdown` completes
+///
+/// Files already in the directory are processed first, then each file
+/// once it settles. Two PDFs with the same name in different subdirectories
+/// would write the same output file: the run fails if they are both there at
+/// the start, and a new file taking an existing file's name is not processed.
+/// Either way, a file is skipped if the manifest shows its output is up to
+/// date, unless `options.force` is set. On shutdown, files being processed
+/// are finished before returning; files not started yet or still settling
+/// are left for the next run.
+///
+/// # Arguments
+/// * `ctx` - Configuration and providers of the run
+/// * `input_dir` - Directory to watch
+/// * `output_dir` - Directory where JSON output files will be saved
+/// * `options` - Files and sections to process
+/// * `shutdown` - Completes when watching should stop, e.g. on Ctrl-C
+pub async fn watch_directory(
+    ctx: &LlmContext,
+    input_dir: &str,
+    output_dir: &str,
+    options: &ProcessOptions,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Box<dyn Error>> {
+    // Polled from the start, so that a Ctrl-C handler is in place before any work
+    tokio::pin!(shutdown);
+    let started = SystemTime::now();
+
+    std::fs::create_dir_all(output_dir)?;
+    if ctx.config.processing.debugging {
+        std::fs::create_dir_all(DEBUG_MARKDOWN_DIR)?;
+    }
+    tokio::select! {
+        _ = &mut shutdown => return Ok(()),
+        result = preflight(ctx) => result?,
+    }
+    let manifest = Manifest::open(output_dir)?;
+    let journal = RunJournal::open(output_dir)?;
+
+    // Events carry absolute paths, so the glob is matched against the canonical root
+    let root = Path::new(input_dir).canonicalize()?;
+    let pattern = glob::Pattern::new(&options.glob)?;
+
+    // Start watching before the existing files are listed, so that none is missed
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let _ = tx.send(event);
+    })?;
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(&root, mode)?;
+
+    let existing = list_pdfs(&root.to_string_lossy(), &options.glob, options.recursive)?;
+    let mut names: HashMap<OsString, PathBuf> = existing
+        .iter()
+        .map(|path| {
+            (
+                path.file_stem().unwrap_or_default().to_owned(),
+                path.clone(),
+            )
+        })
+        .collect();
+    let stopping = AtomicBool::new(false);
+    let backlog = process_files(
+        ctx, &manifest, &journal, existing, output_dir, options, &stopping,
+    );
+    if process_until_shutdown(backlog, shutdown.as_mut(), &stopping).await {
+        tracing::info!("Stopped before watching {}", input_dir);
+        remove_stale_temp_files(output_dir, started)?;
+        journal.finish()?;
+        return Ok(());
+    }
+    // Once the files found on start are written, as at the end of a run
+    remove_stale_temp_files(output_dir, started)?;
+    tracing::info!("Watching {} for new PDFs", input_dir);
+
+    let debounce = ctx.config.processing.watch_debounce;
+    let mut ticks = tokio::time::interval((debounce / 2).max(Duration::from_millis(50)));
+    let mut pending = PendingFiles::default();
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            event = rx.recv() => match event {
+                Some(Ok(event)) => {
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                        for path in event.paths {
+                            if is_listed(&root, &path, &pattern, options.recursive) {
+                                pending.changed(path, Instant::now());
+                            }
+                        }
+                    }
+                }
+                Some(Err(e)) => tracing::warn!("File watcher error: {}", e),
+                None => return Err("File watcher stopped".into()),
+            },
+            _ = ticks.tick() => {
+                let ready: Vec<_> = pending
+                    .settled(debounce, Instant::now())
+                    .into_iter()
+                    .filter(|path| claim_output_name(&mut names, path))
+                    .collect();
+                if !ready.is_empty() {
+                    let files = process_files(
+                        ctx, &manifest, &journal, ready, output_dir, options, &stopping,
+                    );
+                    if process_until_shutdown(files, shutdown.as_mut(), &stopping).await {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    tracing::info!(
+        "Stopped watching {} ({} files still settling)",
+        input_dir,
+        pending.files.len()
+    );
+    journal.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_settle_once_their_size_is_stable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("acme.pdf");
+        std::fs::write(&path, "partial").unwrap();
+
+        let debounce = Duration::from_secs(2);
+        let start = Instant::now();
+        let mut pending = PendingFiles::default();
+        pending.changed(path.clone(), start);
+        pending.changed(dir.path().join("gone.pdf"), start);
+
+        // Still being written
+        assert!(pending.settled(debounce, start).is_empty());
+        // Quiet long enough, but the size has not been seen before
+        let later = start + debounce;
+        assert!(pending.settled(debounce, later).is_empty());
+        assert_eq!(pending.files.len(), 1);
+
+        // The copy grew in the meantime
+        std::fs::write(&path, "partial and the rest").unwrap();
+        let later = later + debounce;
+        assert!(pending.settled(debounce, later).is_empty());
+
+        let later = later + debounce;
+        assert_eq!(pending.settled(debounce, later), vec![path]);
+        assert!(pending.files.is_empty());
+    }
+
+    #[test]
+    fn test_new_file_cannot_take_an_existing_output_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("2024").join("acme.pdf");
+        let second = dir.path().join("2025").join("acme.pdf");
+        std::fs::create_dir_all(first.parent().unwrap()).unwrap();
+        std::fs::write(&first, "pdf").unwrap();
+
+        let mut names = HashMap::new();
+        assert!(claim_output_name(&mut names, &first));
+        assert!(claim_output_name(&mut names, &first));
+        assert!(!claim_output_name(&mut names, &second));
+
+        // Once the first file is gone, its name is free again
+        std::fs::remove_file(&first).unwrap();
+        assert!(claim_output_name(&mut names, &second));
+        assert_eq!(names[&OsString::from("acme")], second);
+    }
+}
@@ -0,0 +1,38 @@
+//! Recording provider exchanges to a cassette against the mock LLM server
+
+use company_pdf_viewer::config::{Config, Settings};
+use company_pdf_viewer::mock::MockLlmServer;
+use company_pdf_viewer::parser::cassette::{Cassette, CassetteEntry, CassetteMode};
+use company_pdf_viewer::parser::context::LlmContext;
+use company_pdf_viewer::parser::ollama::SectionParser;
+
+#[tokio::test]
+async fn test_record_cassette() {
+    let server = MockLlmServer::start().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("llm.jsonl");
+
+    let settings: Settings = [
+        ("LLM_BACKEND", "ollama"),
+        ("OLLAMA_URL", server.url().as_str()),
+        ("LLM_CACHE", "off"),
+        ("LLM_CASSETTE", path.to_str().unwrap()),
+        ("LLM_CASSETTE_MODE", "record"),
+    ]
+    .into_iter()
+    .collect();
+    let ctx = LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap();
+
+    SectionParser::CompanyDetails
+        .parse(&ctx, "Company Details\nFile No. C12345", "Company Details")
+        .await
+        .unwrap();
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    let entry: CassetteEntry = serde_json::from_str(recorded.lines().next().unwrap()).unwrap();
+    assert_eq!(entry.request["model"], server.requests()[0].body["model"]);
+    assert!(entry.response["message"]["content"].is_string());
+
+    let replay = Cassette::open(&path, CassetteMode::Replay).unwrap();
+    assert_eq!(replay.replay(&entry.key), Some(entry.response));
+}
@@ -0,0 +1,90 @@
+//! Per-section models and escalation to a bigger model on invalid replies
+
+use company_pdf_viewer::config::{Config, Settings};
+use company_pdf_viewer::mock::{sample_from_schema, MockLlmServer};
+use company_pdf_viewer::parser::context::LlmContext;
+use company_pdf_viewer::parser::ollama::SectionParser;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_escalate_on_invalid_fields() {
+    // The small model puts the address in the date column; the big one gets it right
+    let server = MockLlmServer::with_responder(|request| {
+        let mut value = request
+            .schema()
+            .map(sample_from_schema)
+            .unwrap_or(Value::Null);
+        if value.get("officeBearers").is_some() {
+            let date = match request.body["model"].as_str() {
+                Some("big") => "01/02/2003",
+                _ => "PORT LOUIS",
+            };
+            value["officeBearers"] = json!([{
+                "position": "DIRECTOR",
+                "name": "DOE JOHN",
+                "address": "ROYAL ROAD",
+                "country": "MAURITIUS",
+                "appointedDate": date,
+                "entityType": ""
+            }]);
+        }
+        value
+    })
+    .unwrap();
+
+    let settings: Settings = [
+        ("LLM_BACKEND", "ollama"),
+        ("OLLAMA_URL", server.url().as_str()),
+        ("OLLAMA_MODEL", "default"),
+        ("LLM_MODEL_OFFICE_BEARERS", "ollama=small"),
+        ("LLM_ESCALATE_OFFICE_BEARERS", "ollama=big"),
+        ("LLM_CACHE", "off"),
+        ("GROUNDING_POLICY", "off"),
+    ]
+    .into_iter()
+    .collect();
+    let ctx = LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap();
+
+    let mut trace = Vec::new();
+    let parsed = SectionParser::OfficeBearers
+        .parse_traced(
+            &ctx,
+            "Office Bearers\nDIRECTOR DOE JOHN ROYAL ROAD MAURITIUS 01/02/2003",
+            "Office Bearers",
+            &mut trace,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(parsed.escalated_to.as_deref(), Some("ollama big"));
+
+    // Both attempts are kept for the debug trace, with the replies as received
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].model, "small");
+    assert!(trace[0].prompt.contains("DOE JOHN"));
+    assert!(trace[0].schema["properties"]["officeBearers"].is_object());
+    let raw = trace[0].raw_response.as_ref().unwrap();
+    assert!(raw["message"]["content"]
+        .as_str()
+        .unwrap()
+        .contains("PORT LOUIS"));
+    assert_eq!(trace[1].model, "big");
+    assert!(trace.iter().all(|t| t.error.is_none() && !t.cached));
+    assert_eq!(
+        parsed.value["officeBearers"][0]["appointedDate"],
+        "01/02/2003"
+    );
+
+    let parsed = SectionParser::CompanyDetails
+        .parse(&ctx, "Company Details\nFile No. C12345", "Company Details")
+        .await
+        .unwrap();
+    assert_eq!(parsed.escalated_to, None);
+
+    let models: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|r| r.body["model"].as_str().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(models, ["small", "big", "default"]);
+}
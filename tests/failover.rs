@@ -0,0 +1,35 @@
+//! Routing across several providers when one of them is down
+
+use company_pdf_viewer::config::{Config, Settings};
+use company_pdf_viewer::mock::MockLlmServer;
+use company_pdf_viewer::parser::context::LlmContext;
+use company_pdf_viewer::parser::ollama::SectionParser;
+
+#[tokio::test]
+async fn test_failover_to_healthy_provider() {
+    let server = MockLlmServer::start().unwrap();
+    let dead_url = MockLlmServer::start().unwrap().url();
+
+    let providers = format!("ollama=mock@{dead_url}, ollama=mock@{}", server.url());
+    let settings: Settings = [
+        ("LLM_PROVIDERS", providers.as_str()),
+        ("LLM_PROVIDER_MAX_FAILURES", "1"),
+        ("LLM_CACHE", "off"),
+    ]
+    .into_iter()
+    .collect();
+    let ctx = LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap();
+
+    for _ in 0..3 {
+        SectionParser::CompanyDetails
+            .parse(&ctx, "Company Details\nFile No. C12345", "Company Details")
+            .await
+            .unwrap();
+    }
+
+    let stats = ctx.router.stats();
+    assert_eq!(stats[0].failures, 1);
+    assert!(!stats[0].available);
+    assert_eq!(stats[1].requests, 3);
+    assert_eq!(server.requests().len(), 3);
+}
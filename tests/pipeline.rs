@@ -0,0 +1,929 @@
+//! End-to-end tests of the parsing pipeline against the built-in mock LLM
+//! server. No network access or model is needed.
+
+use company_pdf_viewer::api::LlmBackend;
+use company_pdf_viewer::company::OfficeBearerList;
+use company_pdf_viewer::config::{Config, Settings};
+use company_pdf_viewer::mock::{minimal_pdf, sample_from_schema, MockLlmServer};
+use company_pdf_viewer::parser::context::LlmContext;
+use company_pdf_viewer::parser::ollama::{parse_section_with_structured_output, SectionParser};
+use company_pdf_viewer::parser::section::find_section;
+use company_pdf_viewer::processor::batch::{
+    add_examples_from_output, process_pdfs_in_directory, process_single_pdf, validate_output,
+    ProcessOptions,
+};
+use company_pdf_viewer::processor::batch_api::{export_batch_requests, ingest_batch_results};
+use company_pdf_viewer::processor::journal::RunJournal;
+use company_pdf_viewer::processor::watch::watch_directory;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Mock server shared by every test
+static SERVER: Lazy<MockLlmServer> = Lazy::new(|| {
+    let server = MockLlmServer::with_responder(|request| {
+        let mut value = request
+            .schema()
+            .map(sample_from_schema)
+            .unwrap_or(Value::Null);
+        let prompt = request.prompt().unwrap_or_default();
+
+        if value.get("officeBearers").is_some() && prompt.contains("DOE JOHN") {
+            value["officeBearers"] = json!([{
+                "position": "DIRECTOR",
+                "name": "DOE JOHN",
+                "address": "ROYAL ROAD PORT LOUIS",
+                "country": "MAURITIUS",
+                "appointedDate": "01/02/2003",
+                "entityType": ""
+            }]);
+        }
+        if value.get("orgName").is_some() {
+            value["orgFileNo"] = json!("C12345");
+            value["orgName"] = json!("ACME (MAURITIUS) LTD");
+        }
+        value
+    })
+    .expect("mock server should start");
+
+    server.set_models(&["mock-model"]);
+    server
+});
+
+/// Configuration pointing at the mock server, independent of the environment
+fn config() -> Config {
+    let settings: Settings = [
+        ("LLM_BACKEND", "ollama"),
+        ("OLLAMA_URL", SERVER.url().as_str()),
+        ("OLLAMA_MODEL", "mock-model"),
+        ("LLM_CACHE", "off"),
+    ]
+    .into_iter()
+    .collect();
+    Config::from_settings(&settings).unwrap()
+}
+
+fn context() -> LlmContext {
+    LlmContext::new(config()).unwrap()
+}
+
+/// Context for a test's own mock server, with the response cache on in `cache_dir`
+fn cached_context(server: &MockLlmServer, cache_dir: &std::path::Path) -> LlmContext {
+    let settings: Settings = [
+        ("LLM_BACKEND", "ollama"),
+        ("OLLAMA_URL", server.url().as_str()),
+        ("OLLAMA_MODEL", "mock-model"),
+        ("LLM_CACHE", "on"),
+        ("LLM_CACHE_DIR", cache_dir.to_str().unwrap()),
+    ]
+    .into_iter()
+    .collect();
+    LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_parse_section_against_mock_server() {
+    let section = "Office Bearers\nDIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003";
+    let parsed = SectionParser::OfficeBearers
+        .parse(&context(), section, "Office Bearers")
+        .await
+        .unwrap();
+
+    assert_eq!(parsed.value["officeBearers"][0]["name"], "DOE JOHN");
+    assert!(parsed.ungrounded.is_empty());
+    assert_eq!(parsed.confidence["/officeBearers/0/name"], 0.9);
+
+    let request = SERVER
+        .requests()
+        .into_iter()
+        .find(|r| r.prompt().is_some_and(|p| p.contains(section)))
+        .expect("request should reach the mock server");
+    assert_eq!(request.path, "/api/chat");
+    let schema = request.schema().unwrap().to_string();
+    assert!(schema.contains("officeBearers"));
+    assert!(!schema.contains("$ref"));
+    assert!(request
+        .prompt()
+        .unwrap()
+        .contains("- officeBearers[].position: Role only"));
+}
+
+#[tokio::test]
+async fn test_malformed_reply_is_not_cached() {
+    static MALFORMED: AtomicBool = AtomicBool::new(true);
+    let server = MockLlmServer::with_responder(|request| {
+        if MALFORMED.load(Ordering::SeqCst) {
+            return json!({ "officeBearers": "not a list" });
+        }
+        request
+            .schema()
+            .map(sample_from_schema)
+            .unwrap_or(Value::Null)
+    })
+    .unwrap();
+    server.set_models(&["mock-model"]);
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = cached_context(&server, dir.path());
+    let prompt = "List the office bearers".to_string();
+
+    let first =
+        parse_section_with_structured_output::<OfficeBearerList>(&ctx, prompt.clone()).await;
+    assert!(first.is_err());
+
+    // The same request is sent again instead of replaying the malformed reply
+    MALFORMED.store(false, Ordering::SeqCst);
+    let (second, _) =
+        parse_section_with_structured_output::<OfficeBearerList>(&ctx, prompt.clone())
+            .await
+            .unwrap();
+    assert!(second.office_bearers.is_empty());
+    assert_eq!(server.requests().len(), 2);
+
+    // A valid reply is cached
+    parse_section_with_structured_output::<OfficeBearerList>(&ctx, prompt)
+        .await
+        .unwrap();
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_process_directory_offline() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(
+        input_dir.join("acme.pdf"),
+        minimal_pdf(&[
+            "Company Details",
+            "File No. C12345",
+            "Name ACME (MAURITIUS) LTD",
+            "Business Details",
+            "Office Bearers",
+            "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+        ]),
+    )
+    .unwrap();
+
+    process_pdfs_in_directory(
+        &context(),
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &ProcessOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let output: Value =
+        serde_json::from_slice(&std::fs::read(output_dir.join("acme.json")).unwrap()).unwrap();
+    assert_eq!(output["filename"], "acme");
+    assert_eq!(output["companyDetails"]["orgName"], "ACME (MAURITIUS) LTD");
+    assert_eq!(output["officeBearers"][0]["name"], "DOE JOHN");
+    assert!(output["businessDetails"].is_array());
+    assert!(output["tokenUsage"].is_object());
+    assert!(validate_output(output.as_object().unwrap()).is_empty());
+}
+
+#[tokio::test]
+async fn test_single_file_matches_batch_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    let pdf_path = input_dir.join("gamma.pdf");
+    std::fs::write(
+        &pdf_path,
+        minimal_pdf(&[
+            "Company Details",
+            "File No. C24680",
+            "Office Bearers",
+            "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+        ]),
+    )
+    .unwrap();
+
+    let ctx = context();
+    let single = process_single_pdf(&ctx, pdf_path.to_str().unwrap(), None)
+        .await
+        .unwrap();
+    process_pdfs_in_directory(
+        &ctx,
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &ProcessOptions::default(),
+    )
+    .await
+    .unwrap();
+    let batch: Value =
+        serde_json::from_slice(&std::fs::read(output_dir.join("gamma.json")).unwrap()).unwrap();
+
+    assert_eq!(Value::Object(single), batch);
+}
+
+#[tokio::test]
+async fn test_corrected_output_becomes_an_example() {
+    let dir = tempfile::tempdir().unwrap();
+    let examples_dir = dir.path().join("examples");
+    let pdf_path = dir.path().join("hotel.pdf");
+    std::fs::write(
+        &pdf_path,
+        minimal_pdf(&[
+            "Company Details",
+            "File No. C97531",
+            "Office Bearers",
+            "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+        ]),
+    )
+    .unwrap();
+    let pdf_path = pdf_path.to_str().unwrap();
+    let ctx = || {
+        let settings: Settings = [
+            ("LLM_BACKEND", "ollama"),
+            ("OLLAMA_URL", SERVER.url().as_str()),
+            ("OLLAMA_MODEL", "mock-model"),
+            ("LLM_CACHE", "off"),
+            ("FEW_SHOT_DIR", examples_dir.to_str().unwrap()),
+        ]
+        .into_iter()
+        .collect();
+        LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap()
+    };
+
+    let mut output = process_single_pdf(&ctx(), pdf_path, None).await.unwrap();
+    output["officeBearers"][0]["name"] = json!("DOE JOHN PAUL");
+    let office_bearers = find_section("office_bearers").unwrap();
+    let added =
+        add_examples_from_output(&ctx(), pdf_path, &output, Some(&[office_bearers])).unwrap();
+    assert_eq!(added, 1);
+    let bank = std::fs::read_to_string(examples_dir.join("office_bearers.jsonl")).unwrap();
+    assert!(bank.contains("DOE JOHN PAUL"), "{}", bank);
+    assert!(!examples_dir.join("company_details.jsonl").exists());
+
+    // The next run shows the corrected table to the model
+    let output = process_single_pdf(&ctx(), pdf_path, None).await.unwrap();
+    assert!(output["promptVersions"]["Office Bearers"]
+        .as_str()
+        .unwrap()
+        .contains("office_bearers.examples@"));
+    assert!(SERVER
+        .requests()
+        .iter()
+        .filter_map(|request| request.prompt())
+        .any(|prompt| prompt.contains("DOE JOHN PAUL")));
+}
+
+#[tokio::test]
+async fn test_process_options_select_files_and_sections() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(input_dir.join("2024")).unwrap();
+    let pdf = minimal_pdf(&[
+        "Company Details",
+        "File No. C12345",
+        "Office Bearers",
+        "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+    ]);
+    std::fs::write(input_dir.join("top.pdf"), &pdf).unwrap();
+    std::fs::write(input_dir.join("2024").join("nested.pdf"), &pdf).unwrap();
+    std::fs::write(input_dir.join("2024").join("other.pdf"), &pdf).unwrap();
+
+    let process = |options: ProcessOptions| {
+        let input_dir = input_dir.to_str().unwrap().to_string();
+        let output_dir = output_dir.to_str().unwrap().to_string();
+        async move {
+            process_pdfs_in_directory(&context(), &input_dir, &output_dir, &options)
+                .await
+                .unwrap()
+        }
+    };
+    let read = |name: &str| -> Value {
+        serde_json::from_slice(&std::fs::read(output_dir.join(name)).unwrap()).unwrap()
+    };
+
+    process(ProcessOptions {
+        sections: vec![4],
+        glob: "2024/n*.pdf".to_string(),
+        recursive: true,
+        ..Default::default()
+    })
+    .await;
+    assert!(!output_dir.join("top.json").exists());
+    assert!(!output_dir.join("other.json").exists());
+    let nested = read("nested.json");
+    assert_eq!(nested["officeBearers"][0]["name"], "DOE JOHN");
+    assert!(nested.get("companyDetails").is_none());
+
+    // Up-to-date output is kept unless forced
+    let only_nested = ProcessOptions {
+        sections: vec![4],
+        glob: "2024/n*.pdf".to_string(),
+        recursive: true,
+        ..Default::default()
+    };
+    std::fs::write(output_dir.join("nested.json"), r#"{"marker": true}"#).unwrap();
+    process(only_nested.clone()).await;
+    assert_eq!(read("nested.json")["marker"], true);
+    process(ProcessOptions {
+        force: true,
+        ..only_nested
+    })
+    .await;
+    assert!(read("nested.json").get("marker").is_none());
+
+    // Other sections or a replaced PDF make the output out of date
+    let all = ProcessOptions {
+        recursive: true,
+        ..Default::default()
+    };
+    process(all.clone()).await;
+    assert!(read("nested.json").get("companyDetails").is_some());
+    assert!(read("top.json").get("companyDetails").is_some());
+
+    std::fs::write(
+        input_dir.join("top.pdf"),
+        minimal_pdf(&["Company Details", "File No. C12345"]),
+    )
+    .unwrap();
+    process(all).await;
+    assert_eq!(read("top.json")["officeBearers"], json!(null));
+    assert_eq!(read("nested.json")["officeBearers"][0]["name"], "DOE JOHN");
+
+    let entry = read(".state/manifest/nested.json");
+    assert_eq!(entry["sections"].as_object().unwrap().len(), 3);
+    let entry = read(".state/manifest/top.json");
+    assert_eq!(entry["status"], "done");
+    // The model that answered and the options it was sent
+    let details = &entry["sections"]["Company Details"];
+    assert_eq!(details["model"], "ollama=mock-model");
+    assert!(details["options"]["num_ctx"].is_u64());
+
+    // Two PDFs named alike in different subdirectories would share an output file
+    std::fs::create_dir_all(input_dir.join("2025")).unwrap();
+    std::fs::write(input_dir.join("2025").join("nested.pdf"), &pdf).unwrap();
+    let err = process_pdfs_in_directory(
+        &context(),
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &ProcessOptions {
+            recursive: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("2024/nested.pdf, 2025/nested.pdf"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_interrupted_file_is_redone() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(
+        input_dir.join("delta.pdf"),
+        minimal_pdf(&["Company Details", "File No. C13579"]),
+    )
+    .unwrap();
+    let (input, output) = (input_dir.to_str().unwrap(), output_dir.to_str().unwrap());
+    let options = ProcessOptions::default();
+
+    process_pdfs_in_directory(&context(), input, output, &options)
+        .await
+        .unwrap();
+    std::fs::write(output_dir.join("delta.json"), r#"{"marker": true}"#).unwrap();
+
+    // A run killed while writing delta: its start is journaled, its end is
+    // not, and temporary files are left behind next to the output and the
+    // run state
+    RunJournal::open(output).unwrap().start("delta").unwrap();
+    let state_dir = output_dir.join(".state");
+    let stale = [
+        output_dir.join(".delta.json.1.0.tmp"),
+        state_dir.join("manifest").join(".delta.json.1.1.tmp"),
+        state_dir
+            .join("sections")
+            .join("delta")
+            .join(".0.json.1.2.tmp"),
+    ];
+    for path in &stale {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "{\"trunc").unwrap();
+    }
+    // A run started on the same directory after this one is still writing
+    let live = output_dir.join(".echo.json.2.0.tmp");
+    std::fs::File::create(&live)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(3600))
+        .unwrap();
+
+    process_pdfs_in_directory(&context(), input, output, &options)
+        .await
+        .unwrap();
+    let output: Value =
+        serde_json::from_slice(&std::fs::read(output_dir.join("delta.json")).unwrap()).unwrap();
+    assert_eq!(output["companyDetails"]["orgName"], "ACME (MAURITIUS) LTD");
+    assert!(stale.iter().all(|path| !path.exists()));
+    assert!(live.exists());
+
+    let journal = std::fs::read_to_string(output_dir.join(".state").join("journal.jsonl")).unwrap();
+    assert!(journal.lines().last().unwrap().contains("\"runEnd\""));
+}
+
+#[tokio::test]
+async fn test_failed_file_does_not_stop_the_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(input_dir.join("broken.pdf"), b"not a pdf").unwrap();
+    std::fs::write(
+        input_dir.join("foxtrot.pdf"),
+        minimal_pdf(&["Company Details", "File No. C24680"]),
+    )
+    .unwrap();
+
+    process_pdfs_in_directory(
+        &context(),
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &ProcessOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    assert!(output_dir.join("foxtrot.json").exists());
+    assert!(!output_dir.join("broken.json").exists());
+    let entry: Value = serde_json::from_slice(
+        &std::fs::read(output_dir.join(".state/manifest/broken.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(entry["status"], "failed");
+    assert!(entry["error"].is_string(), "{}", entry);
+}
+
+#[tokio::test]
+async fn test_batch_export_and_ingest() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(
+        input_dir.join("beta.pdf"),
+        minimal_pdf(&[
+            "Company Details",
+            "File No. C67890",
+            "Office Bearers",
+            "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+        ]),
+    )
+    .unwrap();
+    std::fs::write(
+        input_dir.join("zeta.pdf"),
+        minimal_pdf(&[
+            "Company Details",
+            "File No. C24680",
+            "Office Bearers",
+            "SECRETARY ROE JANE CUREPIPE MAURITIUS 04/05/2006",
+        ]),
+    )
+    .unwrap();
+    std::fs::write(
+        input_dir.join("acme..mu.pdf"),
+        minimal_pdf(&["Company Details", "File No. C11223"]),
+    )
+    .unwrap();
+
+    let ctx = context();
+    let requests_path = dir.path().join("requests.jsonl");
+    let count = export_batch_requests(
+        &ctx,
+        input_dir.to_str().unwrap(),
+        requests_path.to_str().unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(count, 5);
+
+    // Answer every request the way the batch endpoint would, after a line
+    // cut short
+    let mut results = String::from("{\"custom_id\": \"beta::\n");
+    for line in std::fs::read_to_string(&requests_path).unwrap().lines() {
+        let request: Value = serde_json::from_str(line).unwrap();
+        assert_eq!(request["url"], "/v1/responses");
+        let schema = &request["body"]["response_format"]["json_schema"];
+        assert_eq!(schema["strict"], true);
+        assert!(!schema.to_string().contains("$ref"));
+
+        if request["custom_id"] == "zeta::4" {
+            let failed = json!({
+                "custom_id": "zeta::4",
+                "response": null,
+                "error": { "code": "server_error", "message": "try again" }
+            });
+            results.push_str(&format!("{failed}\n"));
+            continue;
+        }
+        let result = json!({
+            "custom_id": request["custom_id"],
+            "response": {
+                "status_code": 200,
+                "body": {
+                    "model": request["body"]["model"],
+                    "metadata": request["body"]["metadata"],
+                    "output_parsed": sample_from_schema(&schema["schema"])
+                }
+            },
+            "error": null
+        });
+        results.push_str(&format!("{result}\n"));
+    }
+    assert!(results.contains("\"beta::0\"") && results.contains("\"beta::4\""));
+    let results_path = dir.path().join("results.jsonl");
+    std::fs::write(&results_path, results).unwrap();
+
+    let output_dir = dir.path().join("output_json");
+    ingest_batch_results(
+        &ctx,
+        input_dir.to_str().unwrap(),
+        results_path.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+    )
+    .await
+    .unwrap();
+
+    // Same keys as the output of the synchronous pipeline
+    let output: Value =
+        serde_json::from_slice(&std::fs::read(output_dir.join("beta.json")).unwrap()).unwrap();
+    assert_eq!(output["filename"], "beta");
+    assert!(output["companyDetails"].is_object());
+    assert_eq!(output["officeBearers"], json!([]));
+    assert_eq!(
+        output["promptVersions"]["Office Bearers"],
+        SectionParser::OfficeBearers.prompt_version(&ctx, LlmBackend::OpenAI)
+    );
+    assert!(output["confidence"]["Company Details"].is_object());
+    assert!(output["tokenUsage"]["sections"]["Office Bearers"].is_object());
+
+    // The failed request is listed like a failed section of `process`
+    let read = |name: &str| -> Value {
+        serde_json::from_slice(&std::fs::read(output_dir.join(name)).unwrap()).unwrap()
+    };
+    let zeta = read("zeta.json");
+    assert!(zeta["companyDetails"].is_object());
+    let error = zeta["errors"]["Office Bearers"].as_str().unwrap();
+    assert!(error.contains("try again"), "{}", error);
+    assert_eq!(read(".state/manifest/zeta.json")["status"], "partial");
+    assert_eq!(read("acme..mu.json")["filename"], "acme..mu");
+
+    // Recorded in the manifest, so a synchronous run does not extract it again
+    std::fs::write(output_dir.join("beta.json"), r#"{"marker": true}"#).unwrap();
+    process_pdfs_in_directory(
+        &ctx,
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &ProcessOptions::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(read("beta.json")["marker"], true);
+
+    // Only the failed section of zeta is sent again
+    let sent: Vec<_> = SERVER
+        .requests()
+        .into_iter()
+        .filter_map(|request| request.prompt().map(str::to_string))
+        .collect();
+    assert!(sent.iter().any(|prompt| prompt.contains("ROE JANE")));
+    assert!(!sent.iter().any(|prompt| prompt.contains("C24680")));
+    let zeta = read("zeta.json");
+    assert!(zeta["officeBearers"].is_array());
+    assert!(zeta.get("errors").is_none());
+    assert_eq!(read(".state/manifest/zeta.json")["status"], "done");
+}
+
+#[tokio::test]
+async fn test_watch_processes_new_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    let pdf = minimal_pdf(&[
+        "Office Bearers",
+        "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+    ]);
+    std::fs::write(input_dir.join("before.pdf"), &pdf).unwrap();
+
+    let mut config = config();
+    config.processing.watch_debounce = Duration::from_millis(100);
+    let ctx = LlmContext::new(config).unwrap();
+
+    let options = ProcessOptions::default();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let watch = watch_directory(
+        &ctx,
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &options,
+        async {
+            stopped.await.ok();
+        },
+    );
+    let drop_files = async {
+        let wait_for = |name: &'static str| {
+            let path = output_dir.join(name);
+            async move {
+                for _ in 0..100 {
+                    if path.exists() {
+                        return true;
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                false
+            }
+        };
+        assert!(wait_for("before.json").await);
+
+        // Written in two steps, as a slow copy would
+        let path = input_dir.join("after.pdf");
+        std::fs::write(&path, &pdf[..100]).unwrap();
+        std::fs::write(&path, &pdf).unwrap();
+        std::fs::write(input_dir.join("notes.txt"), "not a pdf").unwrap();
+        assert!(wait_for("after.json").await);
+        stop.send(()).unwrap();
+    };
+
+    let (result, ()) = tokio::join!(watch, drop_files);
+    result.unwrap();
+
+    let output: Value =
+        serde_json::from_slice(&std::fs::read(output_dir.join("after.json")).unwrap()).unwrap();
+    assert_eq!(output["officeBearers"][0]["name"], "DOE JOHN");
+    assert!(!output_dir.join("notes.json").exists());
+}
+
+#[tokio::test]
+async fn test_watch_stops_during_the_initial_backlog() {
+    // Slow replies, so that shutdown is seen while the first file is in progress
+    static ASKED: AtomicBool = AtomicBool::new(false);
+    let server = MockLlmServer::with_responder(|request| {
+        ASKED.store(true, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(200));
+        request
+            .schema()
+            .map(sample_from_schema)
+            .unwrap_or(Value::Null)
+    })
+    .unwrap();
+    server.set_models(&["mock-model"]);
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = cached_context(&server, &dir.path().join("cache"));
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    let pdf = minimal_pdf(&["Company Details", "File No. C24680"]);
+    for name in ["a.pdf", "b.pdf", "c.pdf"] {
+        std::fs::write(input_dir.join(name), &pdf).unwrap();
+    }
+
+    // Ctrl-C while the first of the existing files is being processed
+    let shutdown = async {
+        while !ASKED.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    watch_directory(
+        &ctx,
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &ProcessOptions::default(),
+        shutdown,
+    )
+    .await
+    .unwrap();
+
+    // The file in progress is finished, the others are left for the next run
+    assert!(output_dir.join("a.json").exists());
+    assert!(!output_dir.join("b.json").exists());
+    assert!(!output_dir.join("c.json").exists());
+}
+
+#[tokio::test]
+async fn test_watch_stops_while_new_files_are_processed() {
+    // Slow replies, so that shutdown is seen while the first file is in progress
+    static ASKED: AtomicBool = AtomicBool::new(false);
+    let server = MockLlmServer::with_responder(|request| {
+        ASKED.store(true, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(200));
+        request
+            .schema()
+            .map(sample_from_schema)
+            .unwrap_or(Value::Null)
+    })
+    .unwrap();
+    server.set_models(&["mock-model"]);
+    let settings: Settings = [
+        ("LLM_BACKEND", "ollama"),
+        ("OLLAMA_URL", server.url().as_str()),
+        ("OLLAMA_MODEL", "mock-model"),
+        ("LLM_CACHE", "off"),
+        ("WATCH_DEBOUNCE_MS", "100"),
+    ]
+    .into_iter()
+    .collect();
+    let ctx = LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    // No section to send, so its output shows the watch has started
+    std::fs::write(
+        input_dir.join("first.pdf"),
+        minimal_pdf(&["Nothing to parse"]),
+    )
+    .unwrap();
+
+    // New files, then Ctrl-C while the first of them is being processed
+    let shutdown = async {
+        while !output_dir.join("first.json").exists() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let pdf = minimal_pdf(&["Company Details", "File No. C97531"]);
+        for name in ["a.pdf", "b.pdf", "c.pdf"] {
+            std::fs::write(input_dir.join(name), &pdf).unwrap();
+        }
+        while !ASKED.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    tokio::time::timeout(
+        Duration::from_secs(30),
+        watch_directory(
+            &ctx,
+            input_dir.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+            &ProcessOptions::default(),
+            shutdown,
+        ),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    // The file in progress is finished, the others are left for the next run
+    let written = ["a.json", "b.json", "c.json"]
+        .into_iter()
+        .filter(|name| output_dir.join(name).exists())
+        .count();
+    assert_eq!(written, 1);
+}
+
+#[tokio::test]
+async fn test_failed_section_is_retried_alone() {
+    static BEARERS_BROKEN: AtomicBool = AtomicBool::new(true);
+    let server = MockLlmServer::with_responder(|request| {
+        let value = request
+            .schema()
+            .map(sample_from_schema)
+            .unwrap_or(Value::Null);
+        if value.get("officeBearers").is_some() && BEARERS_BROKEN.load(Ordering::SeqCst) {
+            return json!({ "officeBearers": "not a list" });
+        }
+        value
+    })
+    .unwrap();
+    server.set_models(&["mock-model"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    // The broken reply must not be cached, or the retry would get it again
+    let ctx = cached_context(&server, &dir.path().join("cache"));
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(
+        input_dir.join("epsilon.pdf"),
+        minimal_pdf(&[
+            "Company Details",
+            "File No. C11223",
+            "Office Bearers",
+            "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+        ]),
+    )
+    .unwrap();
+    let (input, output) = (input_dir.to_str().unwrap(), output_dir.to_str().unwrap());
+    let options = ProcessOptions::default();
+    let read = |name: &str| -> Value {
+        serde_json::from_slice(&std::fs::read(output_dir.join(name)).unwrap()).unwrap()
+    };
+
+    process_pdfs_in_directory(&ctx, input, output, &options)
+        .await
+        .unwrap();
+    let first = read("epsilon.json");
+    assert!(first["companyDetails"].is_object());
+    assert!(first["errors"]["Office Bearers"].is_string());
+    assert_eq!(read(".state/manifest/epsilon.json")["status"], "partial");
+
+    // Only the failed section is sent again
+    BEARERS_BROKEN.store(false, Ordering::SeqCst);
+    let sent = server.requests().len();
+    process_pdfs_in_directory(&ctx, input, output, &options)
+        .await
+        .unwrap();
+    let retried = &server.requests()[sent..];
+    assert!(!retried.is_empty());
+    assert!(retried
+        .iter()
+        .all(|request| request.schema().unwrap()["properties"]
+            .get("officeBearers")
+            .is_some()));
+
+    let second = read("epsilon.json");
+    assert_eq!(second["companyDetails"], first["companyDetails"]);
+    assert!(second["officeBearers"].is_array());
+    assert!(second.get("errors").is_none());
+    assert_eq!(read(".state/manifest/epsilon.json")["status"], "done");
+}
+
+/// Cassette committed with the tests, recorded from [`SERVER`]
+const CASSETTE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cassettes/llm.jsonl");
+
+/// PDF the committed cassette was recorded from
+fn cassette_pdf() -> Vec<u8> {
+    minimal_pdf(&[
+        "Company Details",
+        "File No. C12345",
+        "Name ACME (MAURITIUS) LTD",
+        "Business Details",
+        "Office Bearers",
+        "DIRECTOR DOE JOHN ROYAL ROAD PORT LOUIS MAURITIUS 01/02/2003",
+    ])
+}
+
+fn cassette_context(url: &str, mode: &str) -> LlmContext {
+    let settings: Settings = [
+        ("LLM_BACKEND", "ollama"),
+        ("OLLAMA_URL", url),
+        ("OLLAMA_MODEL", "mock-model"),
+        ("LLM_CACHE", "off"),
+        ("LLM_CASSETTE", CASSETTE),
+        ("LLM_CASSETTE_MODE", mode),
+    ]
+    .into_iter()
+    .collect();
+    LlmContext::new(Config::from_settings(&settings).unwrap()).unwrap()
+}
+
+/// Records the committed cassette again, after a change to the prompts,
+/// schemas or generation options:
+/// `cargo test --test pipeline -- --ignored record_cassette`
+#[tokio::test]
+#[ignore]
+async fn record_cassette() {
+    let _ = std::fs::remove_file(CASSETTE);
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(input_dir.join("acme.pdf"), cassette_pdf()).unwrap();
+
+    process_pdfs_in_directory(
+        &cassette_context(&SERVER.url(), "record"),
+        input_dir.to_str().unwrap(),
+        dir.path().join("output_json").to_str().unwrap(),
+        &ProcessOptions::default(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_replay_cassette() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("pdf");
+    let output_dir = dir.path().join("output_json");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(input_dir.join("acme.pdf"), cassette_pdf()).unwrap();
+
+    // Nothing listens there: every reply must come from the cassette
+    process_pdfs_in_directory(
+        &cassette_context("http://127.0.0.1:9", "replay"),
+        input_dir.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &ProcessOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let output: Value =
+        serde_json::from_slice(&std::fs::read(output_dir.join("acme.json")).unwrap()).unwrap();
+    assert!(
+        output.get("errors").is_none(),
+        "the cassette is out of date, record it again: {}",
+        output["errors"]
+    );
+    assert_eq!(output["companyDetails"]["orgName"], "ACME (MAURITIUS) LTD");
+    assert_eq!(output["officeBearers"][0]["name"], "DOE JOHN");
+}